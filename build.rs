@@ -0,0 +1,96 @@
+//! Validates the WGSL shaders and reflects the layout of the structs in them
+//! other than varyings, so the `#[repr(C)]` types uploaded to the GPU can be
+//! checked against them at compile time. See `src/renderer/wgsl_layout.rs`.
+
+use std::{collections::HashSet, env, fmt::Write, fs, path::Path};
+
+// Only `preprocess` is needed here.
+#[allow(dead_code)]
+#[path = "src/renderer/shaders/preprocessor.rs"]
+mod preprocessor;
+
+const SHADER_DIR: &str = "src/renderer/shaders";
+
+fn main() {
+    println!("cargo:rerun-if-changed={SHADER_DIR}");
+
+    let mut files = fs::read_dir(SHADER_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|file| file.ends_with(".wgsl"))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let load = |file: &str| fs::read_to_string(Path::new(SHADER_DIR).join(file)).ok();
+    let mut seen = HashSet::new();
+    let mut out = String::new();
+    for file in &files {
+        let (source, _) = preprocessor::preprocess(file, &[], &load).unwrap_or_else(|error| {
+            panic!("{SHADER_DIR}/{error}");
+        });
+        let module = match naga::front::wgsl::parse_str(&source) {
+            Ok(module) => module,
+            Err(error) => panic!("{}", error.emit_to_string_with_path(&source, file)),
+        };
+        // The renderer compiles the embedded shaders without defines on the
+        // assumption that this passed.
+        if let Err(error) = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        {
+            panic!("{}", error.emit_to_string_with_path(&source, file));
+        }
+
+        let mut layouter = naga::proc::Layouter::default();
+        layouter.update(module.to_ctx()).unwrap();
+
+        for (handle, ty) in module.types.iter() {
+            let (Some(name), naga::TypeInner::Struct { members, span }) = (&ty.name, &ty.inner)
+            else {
+                continue;
+            };
+            // Varyings only pass between shader stages and are never uploaded.
+            if members.iter().any(|member| member.binding.is_some()) {
+                continue;
+            }
+            // Structs from shared files show up in every file including them.
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+
+            writeln!(
+                out,
+                "pub(crate) const {}: WgslStruct = WgslStruct {{ size: {span}, align: {}, members: &[",
+                to_screaming_snake_case(name),
+                layouter[handle].alignment.round_up(1),
+            )
+            .unwrap();
+            for member in members {
+                writeln!(
+                    out,
+                    "    WgslMember {{ name: {:?}, offset: {} }},",
+                    member.name.as_deref().unwrap_or_default(),
+                    member.offset,
+                )
+                .unwrap();
+            }
+            writeln!(out, "] }};").unwrap();
+        }
+    }
+
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("wgsl_layouts.rs");
+    fs::write(out_path, out).unwrap();
+}
+
+fn to_screaming_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            result.push('_');
+        }
+        result.push(c.to_ascii_uppercase());
+    }
+    result
+}
@@ -0,0 +1,469 @@
+use std::time::Duration;
+
+use crate::{
+    color::Hsla,
+    geometry::{Bounds, Corners, Edges, Point, ScaledPixels, Size},
+};
+
+/// A timing curve mapping linear progress in `[0, 1]` to eased progress.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    /// A CSS-style cubic bezier with control points `(x1, y1)` and `(x2, y2)`.
+    CubicBezier {
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+    },
+    /// A damped spring released from 0 towards 1, sampled over the animation's duration.
+    Spring {
+        stiffness: f32,
+        damping: f32,
+        mass: f32,
+    },
+}
+
+impl Easing {
+    pub const EASE: Self = Self::cubic_bezier(0.25, 0.1, 0.25, 1.0);
+    pub const EASE_IN: Self = Self::cubic_bezier(0.42, 0.0, 1.0, 1.0);
+    pub const EASE_OUT: Self = Self::cubic_bezier(0.0, 0.0, 0.58, 1.0);
+    pub const EASE_IN_OUT: Self = Self::cubic_bezier(0.42, 0.0, 0.58, 1.0);
+
+    pub const fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        Self::CubicBezier { x1, y1, x2, y2 }
+    }
+
+    /// A slightly bouncy spring that settles well within half a second.
+    pub const fn spring() -> Self {
+        Self::Spring {
+            stiffness: 170.0,
+            damping: 26.0,
+            mass: 1.0,
+        }
+    }
+
+    /// Applies the curve to `t`, which is clamped to `[0, 1]`.
+    pub fn apply(&self, t: f32, duration: Duration) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::CubicBezier { x1, y1, x2, y2 } => {
+                if t == 0.0 || t == 1.0 {
+                    return t;
+                }
+                let s = solve_bezier_x(t, x1, x2);
+                bezier(s, y1, y2)
+            }
+            Easing::Spring {
+                stiffness,
+                damping,
+                mass,
+            } => {
+                // The spring is forced to rest on the last frame so the animation
+                // always lands exactly on its target.
+                if t == 1.0 {
+                    return 1.0;
+                }
+                spring(t * duration.as_secs_f32(), stiffness, damping, mass)
+            }
+        }
+    }
+}
+
+fn bezier(s: f32, p1: f32, p2: f32) -> f32 {
+    let inv = 1.0 - s;
+    3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+}
+
+fn bezier_derivative(s: f32, p1: f32, p2: f32) -> f32 {
+    let inv = 1.0 - s;
+    3.0 * inv * inv * p1 + 6.0 * inv * s * (p2 - p1) + 3.0 * s * s * (1.0 - p2)
+}
+
+/// Finds the curve parameter whose x coordinate is `x`, using Newton's method
+/// with a bisection fallback for flat regions.
+fn solve_bezier_x(x: f32, x1: f32, x2: f32) -> f32 {
+    const EPSILON: f32 = 1e-6;
+
+    let mut s = x;
+    for _ in 0..8 {
+        let error = bezier(s, x1, x2) - x;
+        if error.abs() < EPSILON {
+            return s;
+        }
+        let slope = bezier_derivative(s, x1, x2);
+        if slope.abs() < EPSILON {
+            break;
+        }
+        s -= error / slope;
+    }
+
+    let (mut low, mut high) = (0.0, 1.0);
+    s = x;
+    while high - low > EPSILON {
+        if bezier(s, x1, x2) < x {
+            low = s;
+        } else {
+            high = s;
+        }
+        s = (low + high) / 2.0;
+    }
+    s
+}
+
+fn spring(time: f32, stiffness: f32, damping: f32, mass: f32) -> f32 {
+    let omega = (stiffness / mass).sqrt();
+    let zeta = damping / (2.0 * (stiffness * mass).sqrt());
+
+    if zeta < 1.0 {
+        let omega_d = omega * (1.0 - zeta * zeta).sqrt();
+        let envelope = (-zeta * omega * time).exp();
+        1.0 - envelope * ((omega_d * time).cos() + zeta * omega / omega_d * (omega_d * time).sin())
+    } else if zeta == 1.0 {
+        1.0 - (-omega * time).exp() * (1.0 + omega * time)
+    } else {
+        let root = (zeta * zeta - 1.0).sqrt();
+        let r1 = -omega * (zeta - root);
+        let r2 = -omega * (zeta + root);
+        let c2 = r1 / (r2 - r1);
+        let c1 = -1.0 - c2;
+        1.0 + c1 * (r1 * time).exp() + c2 * (r2 * time).exp()
+    }
+}
+
+/// How often an [`Animation`] plays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    Once,
+    Times(u32),
+    Forever,
+}
+
+/// Describes how a value changes over time. Pair it with a [`Transition`] to
+/// actually drive a value from frame callbacks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Animation {
+    pub duration: Duration,
+    pub easing: Easing,
+    pub repeat: Repeat,
+    /// Play every other iteration backwards instead of jumping back to the start.
+    pub alternate: bool,
+}
+
+impl Animation {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            easing: Easing::Linear,
+            repeat: Repeat::Once,
+            alternate: false,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn alternate(mut self) -> Self {
+        self.alternate = true;
+        self
+    }
+
+    /// Returns true once every iteration has played out at `elapsed`.
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        match self.repeat {
+            Repeat::Once => elapsed >= self.duration,
+            Repeat::Times(times) => elapsed >= self.duration * times,
+            Repeat::Forever => false,
+        }
+    }
+
+    /// Returns the eased progress at `elapsed` since the animation started.
+    pub fn progress(&self, elapsed: Duration) -> f32 {
+        if self.duration.is_zero() || self.is_finished(elapsed) {
+            let reversed = self.alternate
+                && match self.repeat {
+                    Repeat::Times(times) => times % 2 == 0,
+                    _ => false,
+                };
+            return if reversed { 0.0 } else { 1.0 };
+        }
+
+        let cycles = elapsed.as_secs_f64() / self.duration.as_secs_f64();
+        let iteration = cycles.floor() as u64;
+        let mut t = cycles.fract() as f32;
+        if self.alternate && iteration % 2 == 1 {
+            t = 1.0 - t;
+        }
+        self.easing.apply(t, self.duration)
+    }
+}
+
+/// Types that can be blended between two values.
+pub trait Interpolate {
+    /// Returns the value `t` of the way from `self` to `other`. `t` may leave
+    /// `[0, 1]` for overshooting curves such as springs.
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for ScaledPixels {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        ScaledPixels(self.0.interpolate(&other.0, t))
+    }
+}
+
+impl Interpolate for Hsla {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        // Go around the hue wheel the short way, so red to magenta doesn't
+        // pass through green.
+        let mut delta = other.h - self.h;
+        if delta > 0.5 {
+            delta -= 1.0;
+        } else if delta < -0.5 {
+            delta += 1.0;
+        }
+
+        Hsla {
+            h: (self.h + delta * t).rem_euclid(1.0),
+            s: self.s.interpolate(&other.s, t).clamp(0.0, 1.0),
+            l: self.l.interpolate(&other.l, t).clamp(0.0, 1.0),
+            a: self.a.interpolate(&other.a, t).clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl<T: Interpolate + Copy> Interpolate for Point<T> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Point {
+            x: self.x.interpolate(&other.x, t),
+            y: self.y.interpolate(&other.y, t),
+        }
+    }
+}
+
+impl<T: Interpolate + Copy> Interpolate for Size<T> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Size {
+            width: self.width.interpolate(&other.width, t),
+            height: self.height.interpolate(&other.height, t),
+        }
+    }
+}
+
+impl<T: Interpolate + Copy> Interpolate for Bounds<T> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Bounds {
+            origin: self.origin.interpolate(&other.origin, t),
+            size: self.size.interpolate(&other.size, t),
+        }
+    }
+}
+
+impl<T: Interpolate + Copy> Interpolate for Corners<T> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Corners {
+            top_left: self.top_left.interpolate(&other.top_left, t),
+            top_right: self.top_right.interpolate(&other.top_right, t),
+            bottom_right: self.bottom_right.interpolate(&other.bottom_right, t),
+            bottom_left: self.bottom_left.interpolate(&other.bottom_left, t),
+        }
+    }
+}
+
+impl<T: Interpolate + Copy> Interpolate for Edges<T> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Edges {
+            top: self.top.interpolate(&other.top, t),
+            right: self.right.interpolate(&other.right, t),
+            bottom: self.bottom.interpolate(&other.bottom, t),
+            left: self.left.interpolate(&other.left, t),
+        }
+    }
+}
+
+/// Drives an [`Animation`] between two values.
+///
+/// It is sampled with the timestamps delivered by `wl_surface.frame` callbacks
+/// rather than a wall clock, so every value lines up with the compositor's
+/// vsync. The first frame after creation (or [`Transition::retarget`]) anchors
+/// the start of the animation.
+#[derive(Clone, Debug)]
+pub struct Transition<T: Interpolate + Clone> {
+    animation: Animation,
+    from: T,
+    to: T,
+    current: T,
+    /// Frame timestamp in milliseconds, as reported by `wl_callback.done`.
+    start: Option<u32>,
+    elapsed: Duration,
+}
+
+impl<T: Interpolate + Clone> Transition<T> {
+    pub fn new(from: T, to: T, animation: Animation) -> Self {
+        Self {
+            animation,
+            current: from.clone(),
+            from,
+            to,
+            start: None,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Advances to the frame presented at `time` (milliseconds, wrapping like
+    /// the Wayland frame callback) and returns the value to draw.
+    pub fn on_frame(&mut self, time: u32) -> &T {
+        let start = *self.start.get_or_insert(time);
+        self.elapsed = Duration::from_millis(time.wrapping_sub(start) as u64);
+        let t = self.animation.progress(self.elapsed);
+        self.current = self.from.interpolate(&self.to, t);
+        &self.current
+    }
+
+    /// The value produced by the last frame.
+    pub fn value(&self) -> &T {
+        &self.current
+    }
+
+    /// Starts moving towards `to` from wherever the animation currently is,
+    /// e.g. when the pointer leaves a widget halfway through its hover effect.
+    pub fn retarget(&mut self, to: T) {
+        self.from = self.current.clone();
+        self.to = to;
+        self.start = None;
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// Whether another frame callback needs to be requested.
+    pub fn is_finished(&self) -> bool {
+        self.start.is_some() && self.animation.is_finished(self.elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in [
+            Easing::Linear,
+            Easing::EASE,
+            Easing::EASE_IN_OUT,
+            Easing::spring(),
+        ] {
+            assert_eq!(easing.apply(0.0, ms(300)), 0.0);
+            assert_eq!(easing.apply(1.0, ms(300)), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_cubic_bezier_matches_linear_when_control_points_are_on_the_diagonal() {
+        let easing = Easing::cubic_bezier(0.25, 0.25, 0.75, 0.75);
+        for t in [0.1, 0.3, 0.5, 0.9] {
+            assert!((easing.apply(t, ms(100)) - t).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_ease_in_out_is_symmetric() {
+        let easing = Easing::EASE_IN_OUT;
+        assert!((easing.apply(0.5, ms(100)) - 0.5).abs() < EPSILON);
+        let a = easing.apply(0.2, ms(100));
+        let b = easing.apply(0.8, ms(100));
+        assert!((a + b - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_spring_settles_on_target() {
+        let easing = Easing::spring();
+        assert!((easing.apply(0.99, ms(1000)) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_repeat_and_alternate() {
+        let animation = Animation::new(ms(100)).repeat(Repeat::Times(2)).alternate();
+        assert!((animation.progress(ms(25)) - 0.25).abs() < EPSILON);
+        assert!((animation.progress(ms(125)) - 0.75).abs() < EPSILON);
+        assert!(!animation.is_finished(ms(199)));
+        assert!(animation.is_finished(ms(200)));
+        assert_eq!(animation.progress(ms(200)), 0.0);
+
+        let forever = Animation::new(ms(100)).repeat(Repeat::Forever);
+        assert!((forever.progress(ms(1050)) - 0.5).abs() < EPSILON);
+        assert!(!forever.is_finished(ms(100_000)));
+    }
+
+    #[test]
+    fn test_hsla_interpolates_hue_the_short_way() {
+        let from = Hsla {
+            h: 0.9,
+            s: 1.0,
+            l: 0.5,
+            a: 1.0,
+        };
+        let to = Hsla {
+            h: 0.1,
+            s: 1.0,
+            l: 0.5,
+            a: 0.0,
+        };
+        let mid = from.interpolate(&to, 0.5);
+        assert!(mid.h.abs() < EPSILON || (mid.h - 1.0).abs() < EPSILON);
+        assert!((mid.a - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_transition_handles_wrapping_frame_times() {
+        let from = Bounds {
+            origin: Point {
+                x: ScaledPixels(0.0),
+                y: ScaledPixels(0.0),
+            },
+            size: Size {
+                width: ScaledPixels(10.0),
+                height: ScaledPixels(10.0),
+            },
+        };
+        let to = Bounds {
+            origin: Point {
+                x: ScaledPixels(100.0),
+                y: ScaledPixels(0.0),
+            },
+            ..from
+        };
+        let mut transition = Transition::new(from, to, Animation::new(ms(100)));
+
+        let start = u32::MAX - 10;
+        assert_eq!(transition.on_frame(start).origin.x, ScaledPixels(0.0));
+        assert_eq!(
+            transition.on_frame(start.wrapping_add(50)).origin.x,
+            ScaledPixels(50.0)
+        );
+        assert!(!transition.is_finished());
+        assert_eq!(
+            transition.on_frame(start.wrapping_add(100)).origin.x,
+            ScaledPixels(100.0)
+        );
+        assert!(transition.is_finished());
+    }
+}
@@ -5,8 +5,11 @@ use serde::de::{self, Deserialize, Deserializer, Visitor};
 use std::{
     fmt::{self, Display, Formatter},
     hash::{Hash, Hasher},
+    sync::Arc,
 };
 
+use crate::geometry::Point;
+
 /// Convert an RGB hex color code number to a color type
 pub fn rgb(hex: u32) -> Rgba {
     let r = ((hex >> 16) & 0xFF) as f32 / 255.0;
@@ -24,17 +27,6 @@ pub fn rgba(hex: u32) -> Rgba {
     Rgba { r, g, b, a }
 }
 
-/// Swap from RGBA with premultiplied alpha to BGRA
-pub(crate) fn swap_rgba_pa_to_bgra(color: &mut [u8]) {
-    color.swap(0, 2);
-    if color[3] > 0 {
-        let a = color[3] as f32 / 255.;
-        color[0] = (color[0] as f32 / a) as u8;
-        color[1] = (color[1] as f32 / a) as u8;
-        color[2] = (color[2] as f32 / a) as u8;
-    }
-}
-
 /// An RGBA color
 #[derive(PartialEq, Clone, Copy, Default)]
 pub struct Rgba {
@@ -60,14 +52,14 @@ impl Rgba {
         if other.a >= 1.0 {
             other
         } else if other.a <= 0.0 {
-            return *self;
+            *self
         } else {
-            return Rgba {
+            Rgba {
                 r: (self.r * (1.0 - other.a)) + (other.r * other.a),
                 g: (self.g * (1.0 - other.a)) + (other.g * other.a),
                 b: (self.b * (1.0 - other.a)) + (other.b * other.a),
                 a: self.a,
-            };
+            }
         }
     }
 }
@@ -335,16 +327,6 @@ pub const fn transparent_black() -> Hsla {
     }
 }
 
-/// Transparent black in [`Hsla`]
-pub const fn transparent_white() -> Hsla {
-    Hsla {
-        h: 0.,
-        s: 0.,
-        l: 1.,
-        a: 0.,
-    }
-}
-
 /// Opaque grey in [`Hsla`], values will be clamped to the range [0, 1]
 pub fn opaque_grey(lightness: f32, opacity: f32) -> Hsla {
     Hsla {
@@ -378,7 +360,7 @@ pub const fn red() -> Hsla {
 /// The color blue in [`Hsla`]
 pub const fn blue() -> Hsla {
     Hsla {
-        h: 0.6666666667,
+        h: 0.6666667,
         s: 1.,
         l: 0.5,
         a: 1.,
@@ -388,7 +370,7 @@ pub const fn blue() -> Hsla {
 /// The color green in [`Hsla`]
 pub const fn green() -> Hsla {
     Hsla {
-        h: 0.3333333333,
+        h: 0.33333334,
         s: 1.,
         l: 0.25,
         a: 1.,
@@ -398,7 +380,7 @@ pub const fn green() -> Hsla {
 /// The color yellow in [`Hsla`]
 pub const fn yellow() -> Hsla {
     Hsla {
-        h: 0.1666666667,
+        h: 0.16666667,
         s: 1.,
         l: 0.5,
         a: 1.,
@@ -463,12 +445,12 @@ impl Hsla {
         if alpha >= 1.0 {
             other
         } else if alpha <= 0.0 {
-            return self;
+            self
         } else {
             let converted_self = Rgba::from(self);
             let converted_other = Rgba::from(other);
             let blended_rgb = converted_self.blend(converted_other);
-            return Hsla::from(blended_rgb);
+            Hsla::from(blended_rgb)
         }
     }
 
@@ -550,6 +532,119 @@ impl<'de> Deserialize<'de> for Hsla {
     }
 }
 
+/// The color space gradients are interpolated in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ColorSpace {
+    /// Mixes the sRGB encoded values, like CSS does by default.
+    #[default]
+    Srgb,
+    /// Mixes light intensities, which keeps the midpoint of two bright colors
+    /// bright.
+    LinearSrgb,
+    /// Perceptually uniform, avoiding the gray midpoints of complementary colors.
+    Oklab,
+}
+
+/// A color at a position along a gradient, between 0 and 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct ColorStop {
+    pub color: Hsla,
+    pub percentage: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub(crate) enum BackgroundTag {
+    Solid,
+    LinearGradient,
+    RadialGradient,
+}
+
+/// What fills a quad: a solid color or a gradient. Only built through its
+/// constructors.
+///
+/// Gradients can have any number of stops. They're shared, so cloning a
+/// background doesn't copy them, and uploaded to their own buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Background {
+    pub(crate) tag: BackgroundTag,
+    pub(crate) color_space: ColorSpace,
+    /// Linear gradients: the direction in degrees, 0 pointing up and 90 right,
+    /// as in CSS.
+    pub(crate) angle: f32,
+    /// Radial gradients: the center, relative to the quad's bounds.
+    pub(crate) center: Point<f32>,
+    pub(crate) solid: Hsla,
+    /// Empty for solid backgrounds.
+    pub(crate) stops: Arc<[ColorStop]>,
+}
+
+impl Eq for Background {}
+
+impl Background {
+    pub fn solid(color: Hsla) -> Self {
+        Self {
+            tag: BackgroundTag::Solid,
+            color_space: ColorSpace::default(),
+            angle: 0.0,
+            center: Point::default(),
+            solid: color,
+            stops: Arc::new([]),
+        }
+    }
+
+    /// A gradient along `angle`, in degrees, reaching the first and last stop
+    /// in opposite corners like CSS `linear-gradient`. Transparent without
+    /// stops.
+    pub fn linear_gradient(angle: f32, stops: &[ColorStop]) -> Self {
+        Self {
+            angle,
+            ..Self::gradient(BackgroundTag::LinearGradient, stops)
+        }
+    }
+
+    /// A circular gradient around `center`, relative to the quad's bounds,
+    /// reaching the last stop in the farthest corner. Transparent without
+    /// stops.
+    pub fn radial_gradient(center: Point<f32>, stops: &[ColorStop]) -> Self {
+        Self {
+            center,
+            ..Self::gradient(BackgroundTag::RadialGradient, stops)
+        }
+    }
+
+    fn gradient(tag: BackgroundTag, stops: &[ColorStop]) -> Self {
+        let Some(first) = stops.first() else {
+            return Self::default();
+        };
+        Self {
+            tag,
+            stops: stops.into(),
+            ..Self::solid(first.color)
+        }
+    }
+
+    /// Interpolates the gradient in `color_space` instead of sRGB.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::solid(transparent_black())
+    }
+}
+
+impl From<Hsla> for Background {
+    fn from(color: Hsla) -> Self {
+        Self::solid(color)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -597,4 +692,12 @@ mod tests {
 
         assert_eq!(actual, rgba(0xdeadbeef))
     }
+
+    #[test]
+    fn test_gradient_without_stops_is_transparent() {
+        let actual = Background::linear_gradient(90.0, &[]);
+
+        assert_eq!(actual.tag, BackgroundTag::Solid);
+        assert_eq!(actual.solid, transparent_black())
+    }
 }
@@ -1,6 +1,8 @@
 use std::fmt;
 
-#[derive(Clone, Copy, PartialEq, PartialOrd)]
+use derive_more::{Add, Sub};
+
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd, Add, Sub)]
 #[repr(transparent)]
 pub struct ScaledPixels(pub(crate) f32);
 
@@ -12,34 +14,183 @@ impl fmt::Debug for ScaledPixels {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Physical pixels of a render target, as used for scissor rects and buffer damage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Add, Sub)]
+#[repr(transparent)]
+pub struct DevicePixels(pub(crate) i32);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 #[repr(C)]
 pub struct Point<T: Copy> {
     pub x: T,
     pub y: T,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 #[repr(C)]
 pub struct Size<T: Copy> {
     pub width: T,
     pub height: T,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 #[repr(C)]
 pub struct Bounds<T: Copy> {
     pub origin: Point<T>,
     pub size: Size<T>,
 }
 
+impl<T> Bounds<T>
+where
+    T: Copy + PartialOrd + Add<Output = T> + Sub<Output = T>,
+{
+    pub fn right(&self) -> T {
+        self.origin.x + self.size.width
+    }
+
+    pub fn bottom(&self) -> T {
+        self.origin.y + self.size.height
+    }
+
+    pub fn from_corners(top_left: Point<T>, bottom_right: Point<T>) -> Self {
+        Bounds {
+            origin: top_left,
+            size: Size {
+                width: bottom_right.x - top_left.x,
+                height: bottom_right.y - top_left.y,
+            },
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.origin.x < self.right() && self.origin.y < self.bottom())
+    }
+
+    /// Returns true if the two bounds share any area.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.origin.x < other.right()
+            && other.origin.x < self.right()
+            && self.origin.y < other.bottom()
+            && other.origin.y < self.bottom()
+    }
+
+    /// The overlapping area of both bounds, which may be empty.
+    pub fn intersect(&self, other: &Self) -> Self {
+        let left = max(self.origin.x, other.origin.x);
+        let top = max(self.origin.y, other.origin.y);
+        let right = max(left, min(self.right(), other.right()));
+        let bottom = max(top, min(self.bottom(), other.bottom()));
+        Self::from_corners(
+            Point { x: left, y: top },
+            Point {
+                x: right,
+                y: bottom,
+            },
+        )
+    }
+
+    /// Grows the bounds by `amount` on every side.
+    pub fn dilate(&self, amount: T) -> Self {
+        Self::from_corners(
+            Point {
+                x: self.origin.x - amount,
+                y: self.origin.y - amount,
+            },
+            Point {
+                x: self.right() + amount,
+                y: self.bottom() + amount,
+            },
+        )
+    }
+
+    /// Returns true if `other` lies entirely within these bounds.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.origin.x <= other.origin.x
+            && self.origin.y <= other.origin.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+
+    /// The smallest bounds containing both.
+    pub fn union(&self, other: &Self) -> Self {
+        let left = min(self.origin.x, other.origin.x);
+        let top = min(self.origin.y, other.origin.y);
+        let right = max(self.right(), other.right());
+        let bottom = max(self.bottom(), other.bottom());
+        Self::from_corners(
+            Point { x: left, y: top },
+            Point {
+                x: right,
+                y: bottom,
+            },
+        )
+    }
+}
+
+impl Size<ScaledPixels> {
+    /// Both radii of a circular corner.
+    pub fn circular(radius: ScaledPixels) -> Self {
+        Size {
+            width: radius,
+            height: radius,
+        }
+    }
+}
+
+impl Bounds<ScaledPixels> {
+    /// The smallest device pixel rectangle covering these bounds.
+    pub fn round_out(&self) -> Bounds<DevicePixels> {
+        let left = self.origin.x.0.floor() as i32;
+        let top = self.origin.y.0.floor() as i32;
+        let right = self.right().0.ceil() as i32;
+        let bottom = self.bottom().0.ceil() as i32;
+        Bounds::from_corners(
+            Point {
+                x: DevicePixels(left),
+                y: DevicePixels(top),
+            },
+            Point {
+                x: DevicePixels(right),
+                y: DevicePixels(bottom),
+            },
+        )
+    }
+}
+
+fn min<T: PartialOrd>(a: T, b: T) -> T {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+fn max<T: PartialOrd>(a: T, b: T) -> T {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(C)]
 pub struct Corners<T: Copy> {
     pub top_left: T,
     pub top_right: T,
-    pub bottom_left: T,
     pub bottom_right: T,
+    pub bottom_left: T,
+}
+
+impl<T: Copy> Corners<T> {
+    pub fn all(value: T) -> Self {
+        Self {
+            top_left: value,
+            top_right: value,
+            bottom_right: value,
+            bottom_left: value,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -50,3 +201,287 @@ pub struct Edges<T: Copy> {
     pub bottom: T,
     pub left: T,
 }
+
+impl<T: Copy> Edges<T> {
+    pub fn all(value: T) -> Self {
+        Self {
+            top: value,
+            right: value,
+            bottom: value,
+            left: value,
+        }
+    }
+}
+
+/// A 2D affine transformation, mapping a point `p` to
+/// `rotation_scale * p + translation`. The matrix is stored by column, as
+/// WGSL's `mat2x2` expects.
+///
+/// Each method applies its transformation before the ones already in the
+/// matrix, like a transform stack, so
+/// `unit().translate(center).rotate(angle).translate(-center)` rotates around
+/// `center`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct TransformationMatrix {
+    pub rotation_scale: [[f32; 2]; 2],
+    pub translation: [f32; 2],
+}
+
+impl Eq for TransformationMatrix {}
+
+impl Default for TransformationMatrix {
+    fn default() -> Self {
+        Self::unit()
+    }
+}
+
+impl TransformationMatrix {
+    /// Leaves every point where it is.
+    pub const fn unit() -> Self {
+        Self {
+            rotation_scale: [[1.0, 0.0], [0.0, 1.0]],
+            translation: [0.0, 0.0],
+        }
+    }
+
+    pub fn translate(self, offset: Point<ScaledPixels>) -> Self {
+        self.compose(Self {
+            rotation_scale: Self::unit().rotation_scale,
+            translation: [offset.x.0, offset.y.0],
+        })
+    }
+
+    /// Rotates around the origin by `radians`, clockwise as the y axis points
+    /// down.
+    pub fn rotate(self, radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        self.compose(Self {
+            rotation_scale: [[cos, sin], [-sin, cos]],
+            translation: [0.0, 0.0],
+        })
+    }
+
+    /// Scales away from the origin, by a different factor along each axis.
+    pub fn scale(self, factor: Size<f32>) -> Self {
+        self.compose(Self {
+            rotation_scale: [[factor.width, 0.0], [0.0, factor.height]],
+            translation: [0.0, 0.0],
+        })
+    }
+
+    /// The transformation applying `other` first, then `self`.
+    pub fn compose(self, other: Self) -> Self {
+        Self {
+            rotation_scale: [
+                self.rotate_and_scale(other.rotation_scale[0]),
+                self.rotate_and_scale(other.rotation_scale[1]),
+            ],
+            translation: self.apply_raw(other.translation),
+        }
+    }
+
+    /// The transformation undoing this one, or `None` if this one collapses
+    /// everything onto a line or a point, e.g. when scaled by zero.
+    pub fn invert(&self) -> Option<Self> {
+        let [[a, b], [c, d]] = self.rotation_scale;
+        let determinant = a * d - b * c;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let inverse = Self {
+            rotation_scale: [
+                [d / determinant, -b / determinant],
+                [-c / determinant, a / determinant],
+            ],
+            translation: [0.0, 0.0],
+        };
+        let [x, y] = inverse.rotate_and_scale(self.translation);
+        Some(Self {
+            translation: [-x, -y],
+            ..inverse
+        })
+    }
+
+    pub fn apply(&self, point: Point<ScaledPixels>) -> Point<ScaledPixels> {
+        let [x, y] = self.apply_raw([point.x.0, point.y.0]);
+        Point {
+            x: ScaledPixels(x),
+            y: ScaledPixels(y),
+        }
+    }
+
+    /// The smallest bounds containing all of `bounds` once transformed.
+    pub fn apply_to_bounds(&self, bounds: Bounds<ScaledPixels>) -> Bounds<ScaledPixels> {
+        if *self == Self::unit() {
+            return bounds;
+        }
+        let corner = |x, y| {
+            let point = self.apply(Point { x, y });
+            Bounds {
+                origin: point,
+                size: Size::default(),
+            }
+        };
+        corner(bounds.origin.x, bounds.origin.y)
+            .union(&corner(bounds.right(), bounds.origin.y))
+            .union(&corner(bounds.right(), bounds.bottom()))
+            .union(&corner(bounds.origin.x, bounds.bottom()))
+    }
+
+    fn rotate_and_scale(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let [column_x, column_y] = self.rotation_scale;
+        [
+            column_x[0] * x + column_y[0] * y,
+            column_x[1] * x + column_y[1] * y,
+        ]
+    }
+
+    fn apply_raw(&self, point: [f32; 2]) -> [f32; 2] {
+        let [x, y] = self.rotate_and_scale(point);
+        [x + self.translation[0], y + self.translation[1]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn point(x: f32, y: f32) -> Point<ScaledPixels> {
+        Point {
+            x: ScaledPixels(x),
+            y: ScaledPixels(y),
+        }
+    }
+
+    fn assert_near(actual: Point<ScaledPixels>, expected: Point<ScaledPixels>) {
+        assert!(
+            (actual.x.0 - expected.x.0).abs() < EPSILON
+                && (actual.y.0 - expected.y.0).abs() < EPSILON,
+            "{actual:?} != {expected:?}",
+        );
+    }
+
+    #[test]
+    fn test_rotate_around_center() {
+        let center = point(50.0, 50.0);
+        let matrix = TransformationMatrix::unit()
+            .translate(center)
+            .rotate(FRAC_PI_2)
+            .translate(point(-50.0, -50.0));
+        // A quarter turn clockwise on screen takes the top right corner to the
+        // bottom right.
+        assert_near(matrix.apply(point(100.0, 0.0)), point(100.0, 100.0));
+        assert_near(matrix.apply(center), center);
+    }
+
+    #[test]
+    fn test_invert_undoes_composition() {
+        let matrix = TransformationMatrix::unit()
+            .translate(point(10.0, -20.0))
+            .rotate(0.7)
+            .scale(Size {
+                width: 2.0,
+                height: 0.5,
+            });
+        let inverse = matrix.invert().unwrap();
+        for position in [point(0.0, 0.0), point(3.0, 4.0), point(-25.0, 100.0)] {
+            assert_near(inverse.apply(matrix.apply(position)), position);
+        }
+        assert_eq!(
+            TransformationMatrix::unit()
+                .scale(Size {
+                    width: 0.0,
+                    height: 1.0
+                })
+                .invert(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_apply_to_bounds() {
+        let bounds = Bounds {
+            origin: point(0.0, 0.0),
+            size: Size {
+                width: ScaledPixels(20.0),
+                height: ScaledPixels(10.0),
+            },
+        };
+        let rotated = TransformationMatrix::unit()
+            .rotate(FRAC_PI_2)
+            .apply_to_bounds(bounds);
+        assert_near(rotated.origin, point(-10.0, 0.0));
+        assert_near(
+            Point {
+                x: rotated.size.width,
+                y: rotated.size.height,
+            },
+            point(10.0, 20.0),
+        );
+    }
+
+    fn device_bounds(left: i32, top: i32, right: i32, bottom: i32) -> Bounds<DevicePixels> {
+        Bounds::from_corners(
+            Point {
+                x: DevicePixels(left),
+                y: DevicePixels(top),
+            },
+            Point {
+                x: DevicePixels(right),
+                y: DevicePixels(bottom),
+            },
+        )
+    }
+
+    #[test]
+    fn test_intersect() {
+        let a = device_bounds(0, 0, 10, 10);
+        assert_eq!(
+            a.intersect(&device_bounds(5, -5, 20, 5)),
+            device_bounds(5, 0, 10, 5)
+        );
+        assert_eq!(
+            a.intersect(&device_bounds(2, 2, 4, 4)),
+            device_bounds(2, 2, 4, 4)
+        );
+        // Disjoint bounds collapse to an empty rect instead of a negative size.
+        let disjoint = a.intersect(&device_bounds(20, 20, 30, 30));
+        assert!(disjoint.is_empty());
+        assert!(disjoint.size.width.0 >= 0 && disjoint.size.height.0 >= 0);
+    }
+
+    #[test]
+    fn test_union() {
+        let a = device_bounds(0, 0, 10, 10);
+        assert_eq!(
+            a.union(&device_bounds(20, -5, 30, 5)),
+            device_bounds(0, -5, 30, 10)
+        );
+        assert_eq!(a.union(&device_bounds(2, 2, 4, 4)), a);
+    }
+
+    #[test]
+    fn test_round_out() {
+        let bounds = Bounds {
+            origin: point(1.5, -2.25),
+            size: Size {
+                width: ScaledPixels(3.0),
+                height: ScaledPixels(2.0),
+            },
+        };
+        assert_eq!(bounds.round_out(), device_bounds(1, -3, 5, 0));
+        let whole = Bounds {
+            origin: point(2.0, 3.0),
+            size: Size {
+                width: ScaledPixels(4.0),
+                height: ScaledPixels(5.0),
+            },
+        };
+        assert_eq!(whole.round_out(), device_bounds(2, 3, 6, 8));
+    }
+}
@@ -1,105 +1,428 @@
-use std::sync::Arc;
+use std::{f32::consts::PI, ops::Range, sync::Arc, time::Duration};
 
-use geometry::{Bounds, Point, ScaledPixels};
+use animation::{Animation, Easing, Interpolate, Repeat, Transition};
+use anyhow::{bail, Context as _};
+use color::{hsla, opaque_grey, rgb, rgba, yellow, Background, ColorSpace, ColorStop, Hsla};
+use geometry::{Bounds, Corners, Edges, Point, ScaledPixels, Size, TransformationMatrix};
+use path_builder::{FillRule, LineCap, LineJoin, PathBuilder, StrokeStyle};
+use renderer::{choose_alpha_mode, context::WgpuContext, shaders, Antialiasing, Renderer};
+use scene::{BorderStyle, Quad, Scene, Underline, UnderlineStyle};
 
+mod animation;
 mod color;
 mod geometry;
+mod path_builder;
 mod renderer;
 mod scene;
 
-fn main() {
-    let gpu_ctx = renderer::context::WgpuContext::new();
-    let mut renderer = renderer::Renderer::new(Arc::new(gpu_ctx));
-
-    let scene = scene::Scene {
-        quads: vec![
-            scene::Quad {
-                order: 1,
-                pad: 0,
-                bounds: Bounds {
-                    origin: geometry::Point {
-                        x: geometry::ScaledPixels(10.0),
-                        y: geometry::ScaledPixels(10.0),
-                    },
-                    size: geometry::Size {
-                        width: geometry::ScaledPixels(200.0),
-                        height: geometry::ScaledPixels(200.0),
-                    },
-                },
-                background: color::Hsla::green(),
-                border_color: color::Hsla::red(),
-                corner_radii: geometry::Corners {
-                    top_left: ScaledPixels(15.0),
-                    top_right: ScaledPixels(15.0),
-                    bottom_left: ScaledPixels(15.0),
-                    bottom_right: ScaledPixels(15.0),
-                },
-                border_widths: geometry::Edges {
-                    top: ScaledPixels(1.0),
-                    bottom: ScaledPixels(1.0),
-                    left: ScaledPixels(1.0),
-                    right: ScaledPixels(1.0),
-                },
+const USAGE: &str = "\
+Renders a demo scene to demo.png.
+
+Options:
+  --backend <names>      comma separated wgpu backends to pick from, e.g. vulkan,gl
+  --power <low|high>     power preference of the adapter
+  --fallback-adapter     only use a software adapter
+  --no-pipeline-cache    don't request the pipeline cache feature
+  --msaa                 multisample every frame, not only those with paths
+  --unorm                render to Rgba8Unorm, encoding sRGB in the shaders
+  --transparent          clear to transparent instead of black
+  --debug-quad-bounds    outline the bounds of every quad
+  --hit-test <x>,<y>     print the draw order of the topmost primitive there
+
+WUI_SHADER_DEV reloads the shaders from the source tree, and
+WUI_SHADER_DEFINES sets comma separated shader defines.";
+
+#[derive(Default)]
+struct Options {
+    backends: Option<wgpu::Backends>,
+    power_preference: Option<wgpu::PowerPreference>,
+    fallback_adapter: bool,
+    no_pipeline_cache: bool,
+    msaa: bool,
+    unorm: bool,
+    transparent: bool,
+    debug_quad_bounds: bool,
+    hit_test: Option<Point<ScaledPixels>>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--backend" => {
+                    options.backends = Some(wgpu::util::parse_backends_from_comma_list(&value()?));
+                }
+                "--power" => {
+                    options.power_preference = Some(match value()?.as_str() {
+                        "low" => wgpu::PowerPreference::LowPower,
+                        "high" => wgpu::PowerPreference::HighPerformance,
+                        other => bail!("unknown power preference {other:?}"),
+                    });
+                }
+                "--fallback-adapter" => options.fallback_adapter = true,
+                "--no-pipeline-cache" => options.no_pipeline_cache = true,
+                "--msaa" => options.msaa = true,
+                "--unorm" => options.unorm = true,
+                "--transparent" => options.transparent = true,
+                "--debug-quad-bounds" => options.debug_quad_bounds = true,
+                "--hit-test" => {
+                    let value = value()?;
+                    let (x, y) = value.split_once(',').context("--hit-test takes <x>,<y>")?;
+                    options.hit_test = Some(point(x.trim().parse()?, y.trim().parse()?));
+                }
+                "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ => bail!("unknown option {arg:?}\n\n{USAGE}"),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    let mut builder = WgpuContext::builder().force_fallback_adapter(options.fallback_adapter);
+    if let Some(backends) = options.backends {
+        builder = builder.backends(backends);
+    }
+    if let Some(power_preference) = options.power_preference {
+        builder = builder.power_preference(power_preference);
+    }
+    if options.no_pipeline_cache {
+        builder = builder.optional_features(wgpu::Features::empty());
+    }
+    let mut renderer = Renderer::new(Arc::new(builder.build()?));
+    let adapter_info = &renderer.gpu_ctx().adapter_info;
+    println!("Using {} ({:?})", adapter_info.name, adapter_info.backend);
+
+    if options.msaa {
+        renderer.set_antialiasing(Antialiasing::Msaa4x);
+    }
+    if options.unorm {
+        renderer.set_format(wgpu::TextureFormat::Rgba8Unorm);
+    }
+    if options.transparent {
+        // PNGs store straight alpha, like a surface that can't premultiply.
+        let supported = [
+            wgpu::CompositeAlphaMode::Opaque,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+        ];
+        renderer.set_alpha_mode(choose_alpha_mode(&supported, true));
+        renderer.set_clear_color(Hsla::transparent_black());
+    }
+    println!(
+        "Rendering to {:?} with {:?} anti-aliasing and {:?} alpha",
+        renderer.format(),
+        renderer.antialiasing(),
+        renderer.alpha_mode()
+    );
+
+    if std::env::var_os("WUI_SHADER_DEV").is_some() {
+        renderer.watch_shaders(concat!(env!("CARGO_MANIFEST_DIR"), "/src/renderer/shaders"));
+    }
+    let mut defines = std::env::var("WUI_SHADER_DEFINES").unwrap_or_default();
+    if options.debug_quad_bounds {
+        defines = format!("{defines},{}", shaders::DEBUG_QUAD_BOUNDS);
+    }
+    let defines = defines
+        .split(',')
+        .filter(|define| !define.is_empty())
+        .collect::<Vec<_>>();
+    if !defines.is_empty() {
+        if let Err(error) = renderer.set_shader_defines(&defines) {
+            eprintln!("{error}");
+        }
+    }
+
+    let scene = demo_scene()?;
+    if let Some(position) = options.hit_test {
+        match scene.hit_test(position) {
+            Some(order) => println!("Hit the primitive with draw order {order} at {position:?}"),
+            None => println!("Nothing to hit at {position:?}"),
+        }
+    }
+
+    renderer.draw(&scene);
+    if let Some(error) = renderer.shader_error() {
+        eprintln!("{error}");
+    }
+    if let Some(error) = renderer.recovery_error() {
+        eprintln!("failed to recover from device loss: {error:#}");
+    }
+    let stats = renderer.upload_stats();
+    println!("Uploaded {} bytes in {} writes", stats.bytes, stats.writes);
+    renderer.save_png("demo.png");
+
+    Ok(())
+}
+
+fn demo_scene() -> anyhow::Result<Scene> {
+    let mut scene = Scene::default();
+
+    // Inserted first, but drawn over everything else like an overlay.
+    scene.push_layer(1);
+    scene.insert(Quad {
+        border_colors: Edges::all(Hsla::red()),
+        border_widths: Edges::all(ScaledPixels(1.0)),
+        corner_radii: Corners::all(Size::circular(ScaledPixels(15.0))),
+        ..quad(bounds(10.0, 10.0, 200.0, 200.0), Hsla::green())
+    });
+    scene.pop_layer();
+
+    let stops = |start: Hsla, end: Hsla| {
+        [
+            ColorStop {
+                color: start,
+                percentage: 0.0,
             },
-            scene::Quad {
-                order: 0,
-                pad: 0,
-                bounds: Bounds {
-                    origin: geometry::Point {
-                        x: geometry::ScaledPixels(250.0),
-                        y: geometry::ScaledPixels(10.0),
-                    },
-                    size: geometry::Size {
-                        width: geometry::ScaledPixels(150.0),
-                        height: geometry::ScaledPixels(150.0),
-                    },
-                },
-                background: color::Hsla::green(),
-                border_color: color::Hsla::red(),
-                corner_radii: geometry::Corners {
-                    top_left: ScaledPixels(15.0),
-                    top_right: ScaledPixels(15.0),
-                    bottom_left: ScaledPixels(15.0),
-                    bottom_right: ScaledPixels(15.0),
-                },
-                border_widths: geometry::Edges {
-                    top: ScaledPixels(1.0),
-                    bottom: ScaledPixels(1.0),
-                    left: ScaledPixels(1.0),
-                    right: ScaledPixels(1.0),
-                },
+            ColorStop {
+                color: end,
+                percentage: 1.0,
             },
-            scene::Quad {
-                order: 0,
-                pad: 0,
-                bounds: Bounds {
-                    origin: geometry::Point {
-                        x: geometry::ScaledPixels(250.0),
-                        y: geometry::ScaledPixels(250.0),
-                    },
-                    size: geometry::Size {
-                        width: geometry::ScaledPixels(150.0),
-                        height: geometry::ScaledPixels(150.0),
-                    },
-                },
-                background: color::Hsla::black(),
-                border_color: color::Hsla::red(),
-                corner_radii: geometry::Corners {
-                    top_left: ScaledPixels(75.0),
-                    top_right: ScaledPixels(75.0),
-                    bottom_left: ScaledPixels(75.0),
-                    bottom_right: ScaledPixels(75.0),
+        ]
+    };
+    scene.insert(Quad {
+        border_style: BorderStyle::Dashed,
+        border_colors: Edges::all(Hsla::red()),
+        border_widths: Edges::all(ScaledPixels(2.0)),
+        border_dash_length: ScaledPixels(8.0),
+        border_dash_gap: ScaledPixels(4.0),
+        corner_radii: Corners::all(Size::circular(ScaledPixels(15.0))),
+        ..quad(
+            bounds(250.0, 10.0, 150.0, 150.0),
+            Background::linear_gradient(135.0, &stops(yellow(), Hsla::blue()))
+                .with_color_space(ColorSpace::Oklab),
+        )
+    });
+    scene.insert(Quad {
+        border_style: BorderStyle::Dotted,
+        border_colors: Edges::all(Hsla::white()),
+        border_widths: Edges::all(ScaledPixels(3.0)),
+        border_dash_gap: ScaledPixels(4.0),
+        corner_radii: Corners::all(Size::circular(ScaledPixels(75.0))),
+        ..quad(
+            bounds(250.0, 250.0, 150.0, 150.0),
+            Background::radial_gradient(
+                Point { x: 0.5, y: 0.5 },
+                &stops(rgb(0xff8800).into(), hsla(0.8, 0.7, 0.4, 1.0)),
+            )
+            .with_color_space(ColorSpace::LinearSrgb),
+        )
+    });
+
+    // A loading spinner 1.1s in: a square turning once a second and pulsing
+    // twice, in a progress arc.
+    let elapsed = Duration::from_millis(1100);
+    let turn = Animation::new(Duration::from_secs(1)).repeat(Repeat::Forever);
+    let pulse = Animation::new(Duration::from_millis(500))
+        .with_easing(Easing::EASE_IN_OUT)
+        .repeat(Repeat::Times(4))
+        .alternate();
+    let scale = 1.0.interpolate(&1.3, pulse.progress(elapsed));
+    let center = point(90.0, 300.0);
+    let around_center = |transformation: TransformationMatrix| {
+        TransformationMatrix::unit()
+            .translate(center)
+            .compose(transformation)
+            .translate(point(-center.x.0, -center.y.0))
+    };
+    scene.insert(Quad {
+        transformation: around_center(
+            TransformationMatrix::unit()
+                .rotate(2.0 * PI * turn.progress(elapsed))
+                .scale(Size {
+                    width: scale,
+                    height: scale,
+                }),
+        ),
+        ..quad(bounds(70.0, 280.0, 40.0, 40.0), Hsla::from(rgb(0x3584e4)))
+    });
+    let mut progress = PathBuilder::new();
+    progress.arc(center, ScaledPixels(50.0), -PI / 2.0, 1.4 * PI);
+    scene.insert(progress.stroke(
+        &StrokeStyle {
+            width: ScaledPixels(6.0),
+            cap: LineCap::Round,
+            ..Default::default()
+        },
+        opaque_grey(0.8, 1.0),
+    )?);
+
+    // A CPU graph, underlined like a link.
+    let mut graph = PathBuilder::new();
+    let load = [0.2, 0.5, 0.35, 0.8, 0.6, 0.9, 0.4, 0.3, 0.7];
+    for (i, load) in load.iter().enumerate() {
+        let to = point(20.0 + i as f32 * 25.0, 480.0 - load * 80.0);
+        if i == 0 {
+            graph.move_to(to);
+        } else {
+            graph.line_to(to);
+        }
+    }
+    scene.insert(graph.stroke(
+        &StrokeStyle {
+            width: ScaledPixels(3.0),
+            join: LineJoin::Round,
+            ..Default::default()
+        },
+        yellow(),
+    )?);
+    scene.insert(Underline {
+        order: 0,
+        pad: 0,
+        bounds: bounds(20.0, 490.0, 200.0, 6.0),
+        color: opaque_grey(0.6, 1.0),
+        thickness: ScaledPixels(2.0),
+        style: UnderlineStyle::Wavy,
+    });
+
+    // The same transition with every easing curve, 40% of the way through.
+    let curves = [
+        Easing::Linear,
+        Easing::EASE,
+        Easing::EASE_IN,
+        Easing::EASE_OUT,
+        Easing::EASE_IN_OUT,
+        Easing::spring(),
+    ];
+    for (i, easing) in curves.into_iter().enumerate() {
+        let y = 20.0 + i as f32 * 25.0;
+        let mut dot = Transition::new(
+            point(415.0, y),
+            point(485.0, y),
+            Animation::new(Duration::from_millis(400)).with_easing(easing),
+        );
+        let origin = play(&mut dot, 0..160);
+        scene.insert(Quad {
+            corner_radii: Corners::all(Size::circular(ScaledPixels(7.5))),
+            ..quad(
+                Bounds {
+                    origin,
+                    ..bounds(0.0, 0.0, 15.0, 15.0)
                 },
-                border_widths: geometry::Edges {
-                    top: ScaledPixels(1.0),
-                    bottom: ScaledPixels(1.0),
-                    left: ScaledPixels(1.0),
-                    right: ScaledPixels(1.0),
+                Hsla::white(),
+            )
+        });
+    }
+
+    // A button the pointer left halfway through its hover effect.
+    let mut hover = Transition::new(
+        opaque_grey(0.3, 1.0),
+        Hsla::blue(),
+        Animation::new(Duration::from_millis(200)).with_easing(Easing::EASE),
+    );
+    play(&mut hover, 0..100);
+    hover.retarget(opaque_grey(0.3, 1.0));
+    let background = play(&mut hover, 100..150);
+    scene.insert(Quad {
+        corner_radii: Corners::all(Size::circular(ScaledPixels(6.0))),
+        ..quad(bounds(415.0, 180.0, 85.0, 30.0), background)
+    });
+
+    // A filled heart icon.
+    let mut heart = PathBuilder::new();
+    heart.move_to(point(457.0, 335.0));
+    heart.quadratic_bezier_to(point(415.0, 300.0), point(420.0, 275.0));
+    heart.cubic_bezier_to(
+        point(425.0, 245.0),
+        point(455.0, 245.0),
+        point(457.0, 270.0),
+    );
+    heart.cubic_bezier_to(
+        point(459.0, 245.0),
+        point(489.0, 245.0),
+        point(494.0, 275.0),
+    );
+    heart.quadratic_bezier_to(point(499.0, 300.0), point(457.0, 335.0));
+    heart.close();
+    scene.insert(heart.fill(FillRule::NonZero, Hsla::red())?);
+
+    // A popup halfway through sliding and fading in.
+    let popup = bounds(270.0, 420.0, 220.0, 70.0);
+    let mut slide = Transition::new(
+        Bounds {
+            origin: point(popup.origin.x.0, 512.0),
+            ..popup
+        },
+        popup,
+        Animation::new(Duration::from_millis(400)).with_easing(Easing::spring()),
+    );
+    let mut fade = Transition::new(
+        0.0,
+        1.0,
+        Animation::new(Duration::from_millis(300)).with_easing(Easing::EASE_OUT),
+    );
+    let popup = play(&mut slide, 0..160);
+    scene.push_opacity_layer(popup, play(&mut fade, 0..160));
+    scene.insert(Quad {
+        corner_radii: Corners::all(Size::circular(ScaledPixels(8.0))),
+        ..quad(popup, Hsla::from(rgba(0x303030e0)))
+    });
+    scene.insert(Quad {
+        corner_radii: Corners::all(Size::circular(ScaledPixels(4.0))),
+        ..quad(
+            Bounds {
+                origin: point(popup.origin.x.0 + 15.0, popup.origin.y.0 + 15.0),
+                size: Size {
+                    width: ScaledPixels(40.0),
+                    height: ScaledPixels(40.0),
                 },
             },
-        ],
-        monochrome_sprites: vec![],
-    };
+            Hsla::red(),
+        )
+    });
+    scene.pop_layer();
 
-    renderer.draw(&scene);
+    scene.finish();
+    Ok(scene)
+}
+
+/// Drives `transition` with the frame callbacks a 60 Hz output would deliver
+/// over `time`, in milliseconds, until it finishes. Returns the last value.
+fn play<T: Interpolate + Clone>(transition: &mut Transition<T>, time: Range<u32>) -> T {
+    for time in time.step_by(16) {
+        transition.on_frame(time);
+        if transition.is_finished() {
+            break;
+        }
+    }
+    transition.value().clone()
+}
+
+fn point(x: f32, y: f32) -> Point<ScaledPixels> {
+    Point {
+        x: ScaledPixels(x),
+        y: ScaledPixels(y),
+    }
+}
+
+fn bounds(x: f32, y: f32, width: f32, height: f32) -> Bounds<ScaledPixels> {
+    Bounds {
+        origin: point(x, y),
+        size: Size {
+            width: ScaledPixels(width),
+            height: ScaledPixels(height),
+        },
+    }
+}
+
+/// A borderless, square cornered quad.
+fn quad(bounds: Bounds<ScaledPixels>, background: impl Into<Background>) -> Quad {
+    Quad {
+        order: 0,
+        border_style: BorderStyle::Solid,
+        bounds,
+        background: background.into(),
+        border_colors: Edges::all(Hsla::transparent_black()),
+        corner_radii: Corners::all(Size::default()),
+        border_widths: Edges::all(ScaledPixels(0.0)),
+        border_dash_length: ScaledPixels(0.0),
+        border_dash_gap: ScaledPixels(0.0),
+        transformation: TransformationMatrix::unit(),
+    }
 }
@@ -0,0 +1,409 @@
+//! Builds vector [`Path`]s, for charts, icons and progress arcs.
+//!
+//! Paths are tessellated into triangles on the CPU with lyon. Their edges have
+//! no analytic coverage, so scenes with paths are always multisampled, see
+//! [`Antialiasing`](crate::renderer::Antialiasing).
+
+use lyon::{
+    math::{point, vector, Angle},
+    path::{builder::WithSvg, iterator::PathIterator, path::BuilderImpl, PathEvent},
+    tessellation::{
+        BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
+        StrokeVertex, VertexBuffers,
+    },
+};
+
+pub use lyon::tessellation::{FillRule, LineCap, LineJoin};
+
+use crate::{
+    color::Hsla,
+    geometry::{Bounds, Point, ScaledPixels, Size, TransformationMatrix},
+    scene::{Path, PathVertex},
+};
+
+/// How far, in pixels, curves may deviate from the line segments they're
+/// flattened into.
+const TOLERANCE: f32 = 0.1;
+
+/// How a [`PathBuilder`] outline is stroked.
+#[derive(Clone, Debug)]
+pub struct StrokeStyle {
+    pub width: ScaledPixels,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Alternating dash and gap lengths, restarting on every sub-path. Empty
+    /// for a solid line.
+    pub dashes: Vec<ScaledPixels>,
+    /// How far into the dash pattern each sub-path starts.
+    pub dash_offset: ScaledPixels,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            width: ScaledPixels(1.0),
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            dashes: Vec::new(),
+            dash_offset: ScaledPixels(0.0),
+        }
+    }
+}
+
+/// Records an outline like an SVG path or a canvas, then fills or strokes it.
+pub struct PathBuilder {
+    builder: WithSvg<BuilderImpl>,
+    started: bool,
+}
+
+impl Default for PathBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PathBuilder {
+    pub fn new() -> Self {
+        Self {
+            builder: lyon::path::Path::builder().with_svg(),
+            started: false,
+        }
+    }
+
+    /// Starts a new sub-path at `to`.
+    pub fn move_to(&mut self, to: Point<ScaledPixels>) {
+        self.builder.move_to(to_lyon(to));
+        self.started = true;
+    }
+
+    pub fn line_to(&mut self, to: Point<ScaledPixels>) {
+        self.builder.line_to(to_lyon(to));
+        self.started = true;
+    }
+
+    pub fn quadratic_bezier_to(&mut self, ctrl: Point<ScaledPixels>, to: Point<ScaledPixels>) {
+        self.builder.quadratic_bezier_to(to_lyon(ctrl), to_lyon(to));
+        self.started = true;
+    }
+
+    pub fn cubic_bezier_to(
+        &mut self,
+        ctrl1: Point<ScaledPixels>,
+        ctrl2: Point<ScaledPixels>,
+        to: Point<ScaledPixels>,
+    ) {
+        self.builder
+            .cubic_bezier_to(to_lyon(ctrl1), to_lyon(ctrl2), to_lyon(to));
+        self.started = true;
+    }
+
+    /// Adds a circular arc like the canvas `arc()`: angles are in radians from
+    /// the x axis, and positive sweeps go clockwise on screen. A line connects
+    /// the current point to the start of the arc, if there is one.
+    pub fn arc(
+        &mut self,
+        center: Point<ScaledPixels>,
+        radius: ScaledPixels,
+        start_angle: f32,
+        sweep_angle: f32,
+    ) {
+        let center = to_lyon(center);
+        let start = center + vector(start_angle.cos(), start_angle.sin()) * radius.0;
+        if self.started {
+            self.builder.line_to(start);
+        } else {
+            self.builder.move_to(start);
+        }
+        self.builder.arc(
+            center,
+            vector(radius.0, radius.0),
+            Angle::radians(sweep_angle),
+            Angle::zero(),
+        );
+        self.started = true;
+    }
+
+    /// Closes the current sub-path with a line back to its start.
+    pub fn close(&mut self) {
+        self.builder.close();
+    }
+
+    /// Fills the outline, deciding what's inside self-intersecting or nested
+    /// sub-paths with `fill_rule`.
+    pub fn fill(self, fill_rule: FillRule, color: Hsla) -> anyhow::Result<Path> {
+        let path = self.builder.build();
+        let mut buffers = VertexBuffers::new();
+        FillTessellator::new().tessellate_path(
+            &path,
+            &FillOptions::tolerance(TOLERANCE).with_fill_rule(fill_rule),
+            &mut BuffersBuilder::new(&mut buffers, |vertex: FillVertex| vertex.position()),
+        )?;
+        Ok(to_path(buffers, color))
+    }
+
+    pub fn stroke(self, style: &StrokeStyle, color: Hsla) -> anyhow::Result<Path> {
+        let mut path = self.builder.build();
+        if style.dashes.iter().any(|dash| dash.0 > 0.0) {
+            path = dash(&path, &style.dashes, style.dash_offset);
+        }
+        let mut buffers = VertexBuffers::new();
+        StrokeTessellator::new().tessellate_path(
+            &path,
+            &StrokeOptions::tolerance(TOLERANCE)
+                .with_line_width(style.width.0)
+                .with_line_join(style.join)
+                .with_line_cap(style.cap),
+            &mut BuffersBuilder::new(&mut buffers, |vertex: StrokeVertex| vertex.position()),
+        )?;
+        Ok(to_path(buffers, color))
+    }
+}
+
+fn to_lyon(position: Point<ScaledPixels>) -> lyon::math::Point {
+    point(position.x.0, position.y.0)
+}
+
+/// Unrolls the indexed triangles, as the path shader reads one vertex per
+/// `vertex_index`.
+fn to_path(buffers: VertexBuffers<lyon::math::Point, u32>, color: Hsla) -> Path {
+    let vertices = buffers
+        .indices
+        .iter()
+        .map(|&index| {
+            let position = buffers.vertices[index as usize];
+            PathVertex {
+                xy_position: Point {
+                    x: ScaledPixels(position.x),
+                    y: ScaledPixels(position.y),
+                },
+                color,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let bounds = vertices
+        .iter()
+        .map(|vertex| Bounds {
+            origin: vertex.xy_position,
+            size: Size::default(),
+        })
+        .reduce(|bounds, vertex| bounds.union(&vertex))
+        .unwrap_or_default();
+
+    Path {
+        order: 0,
+        bounds,
+        transformation: TransformationMatrix::unit(),
+        vertices,
+    }
+}
+
+/// Splits every sub-path of `path` into the dashes of `pattern`, as separate
+/// open sub-paths.
+fn dash(
+    path: &lyon::path::Path,
+    pattern: &[ScaledPixels],
+    offset: ScaledPixels,
+) -> lyon::path::Path {
+    // Like SVG, odd patterns repeat to make dash and gap alternate.
+    let mut pattern = pattern
+        .iter()
+        .map(|length| length.0.max(0.0))
+        .collect::<Vec<_>>();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_within(..);
+    }
+    let mut dasher = Dasher {
+        offset: offset.0.rem_euclid(pattern.iter().sum()),
+        pattern,
+        index: 0,
+        remaining: 0.0,
+        open: false,
+        builder: lyon::path::Path::builder(),
+    };
+
+    for event in path.iter().flattened(TOLERANCE) {
+        match event {
+            PathEvent::Begin { at } => dasher.begin(at),
+            PathEvent::Line { from, to } => dasher.line(from, to),
+            PathEvent::End {
+                last, first, close, ..
+            } => {
+                if close {
+                    dasher.line(last, first);
+                }
+                dasher.end();
+            }
+            PathEvent::Quadratic { .. } | PathEvent::Cubic { .. } => unreachable!("flattened"),
+        }
+    }
+    dasher.builder.build()
+}
+
+struct Dasher {
+    pattern: Vec<f32>,
+    offset: f32,
+    /// The current entry of `pattern`: dashes at even indices, gaps at odd ones.
+    index: usize,
+    /// How much of the current entry is left.
+    remaining: f32,
+    /// Whether a dash sub-path is being built.
+    open: bool,
+    builder: lyon::path::path::Builder,
+}
+
+impl Dasher {
+    fn begin(&mut self, at: lyon::math::Point) {
+        self.index = 0;
+        self.remaining = self.pattern[0];
+        let mut offset = self.offset;
+        while offset > self.remaining {
+            offset -= self.remaining;
+            self.advance();
+        }
+        self.remaining -= offset;
+        if self.index.is_multiple_of(2) {
+            self.builder.begin(at);
+            self.open = true;
+        }
+    }
+
+    fn line(&mut self, mut from: lyon::math::Point, to: lyon::math::Point) {
+        let mut length = (to - from).length();
+        while length > self.remaining {
+            let split = from.lerp(to, self.remaining / length);
+            if self.open {
+                self.builder.line_to(split);
+                self.builder.end(false);
+                self.open = false;
+            } else {
+                self.builder.begin(split);
+                self.open = true;
+            }
+            length -= self.remaining;
+            from = split;
+            self.advance();
+        }
+        self.remaining -= length;
+        if self.open {
+            self.builder.line_to(to);
+        }
+    }
+
+    fn end(&mut self) {
+        if self.open {
+            self.builder.end(false);
+            self.open = false;
+        }
+    }
+
+    fn advance(&mut self) {
+        self.index = (self.index + 1) % self.pattern.len();
+        self.remaining = self.pattern[self.index];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pt(x: f32, y: f32) -> Point<ScaledPixels> {
+        Point {
+            x: ScaledPixels(x),
+            y: ScaledPixels(y),
+        }
+    }
+
+    fn square(builder: &mut PathBuilder, origin: f32, size: f32) {
+        builder.move_to(pt(origin, origin));
+        builder.line_to(pt(origin + size, origin));
+        builder.line_to(pt(origin + size, origin + size));
+        builder.line_to(pt(origin, origin + size));
+        builder.close();
+    }
+
+    fn area(path: &Path) -> f32 {
+        path.vertices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i].xy_position);
+                ((b.x.0 - a.x.0) * (c.y.0 - a.y.0) - (c.x.0 - a.x.0) * (b.y.0 - a.y.0)).abs() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_fill_rules() {
+        let nested_squares = || {
+            let mut builder = PathBuilder::new();
+            square(&mut builder, 0.0, 10.0);
+            square(&mut builder, 2.0, 6.0);
+            builder
+        };
+
+        let non_zero = nested_squares()
+            .fill(FillRule::NonZero, Hsla::red())
+            .unwrap();
+        let even_odd = nested_squares()
+            .fill(FillRule::EvenOdd, Hsla::red())
+            .unwrap();
+
+        assert_eq!(area(&non_zero), 100.0);
+        assert_eq!(area(&even_odd), 64.0);
+        assert_eq!(
+            non_zero.bounds,
+            Bounds::from_corners(pt(0.0, 0.0), pt(10.0, 10.0))
+        );
+    }
+
+    #[test]
+    fn test_dashes() {
+        let mut builder = PathBuilder::new();
+        builder.move_to(pt(0.0, 0.0));
+        builder.line_to(pt(100.0, 0.0));
+        let path = dash(
+            &builder.builder.build(),
+            &[ScaledPixels(10.0), ScaledPixels(15.0)],
+            ScaledPixels(5.0),
+        );
+
+        let dashes = path
+            .iter()
+            .filter_map(|event| match event {
+                PathEvent::End { last, first, .. } => Some((first.x, last.x)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dashes,
+            [
+                (0.0, 5.0),
+                (20.0, 30.0),
+                (45.0, 55.0),
+                (70.0, 80.0),
+                (95.0, 100.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arc_starts_at_start_angle() {
+        let mut builder = PathBuilder::new();
+        builder.arc(
+            pt(50.0, 50.0),
+            ScaledPixels(10.0),
+            0.0,
+            std::f32::consts::PI,
+        );
+        let path = builder
+            .stroke(&StrokeStyle::default(), Hsla::red())
+            .unwrap();
+
+        // Half a circle clockwise from the right, so it bulges downwards. Its
+        // butt caps end flush with the center line.
+        let bounds = path.bounds;
+        assert!((bounds.origin.x.0 - 39.5).abs() < 0.1);
+        assert!((bounds.origin.y.0 - 50.0).abs() < 0.1);
+        assert!((bounds.bottom().0 - 60.5).abs() < 0.1);
+    }
+}
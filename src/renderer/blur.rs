@@ -0,0 +1,186 @@
+use crate::{
+    geometry::{Bounds, DevicePixels, Point},
+    scene::BackdropBlur,
+};
+
+use super::{
+    pipelines::{PipelineVariant, Pipelines},
+    set_scissor_rect,
+};
+
+/// The textures a backdrop blur is computed in, each level half the size of
+/// the one before. Level 0 is the size of the render target and receives a
+/// copy of what's been drawn so far, then the dual Kawase passes go down the
+/// chain and back up to level 1, which backdrop blurs sample.
+///
+/// Kept across frames and only recreated when the target's format or size
+/// changes.
+pub(crate) struct BlurChain {
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+    levels: Vec<BlurLevel>,
+}
+
+struct BlurLevel {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl BlurChain {
+    pub fn new(
+        device: &wgpu::Device,
+        pipelines: &Pipelines,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+    ) -> Self {
+        let levels = (0..=BackdropBlur::MAX_PASSES)
+            .map(|level| {
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("blur texture"),
+                    size: wgpu::Extent3d {
+                        width: (size.width >> level).max(1),
+                        height: (size.height >> level).max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("blur texture bind group"),
+                    layout: &pipelines.texture_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&pipelines.linear_sampler),
+                        },
+                    ],
+                });
+                BlurLevel {
+                    texture,
+                    view,
+                    bind_group,
+                }
+            })
+            .collect();
+
+        Self {
+            format,
+            size,
+            levels,
+        }
+    }
+
+    /// Whether the chain can blur a target of this format and size.
+    pub fn fits(&self, format: wgpu::TextureFormat, size: wgpu::Extent3d) -> bool {
+        self.format == format && self.size == size
+    }
+
+    /// Level 1, holding the result of the last [`BlurChain::blur`].
+    pub fn blurred(&self) -> &wgpu::BindGroup {
+        &self.levels[1].bind_group
+    }
+
+    /// Copies `region` of `source`, the resolved render target, and blurs it
+    /// with `passes` passes down the chain and back up to level 1.
+    ///
+    /// Only `region` is blurred, so it should include everything the blur
+    /// samples. Texels outside of it are left over from earlier blurs.
+    pub fn blur(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        variant: &PipelineVariant,
+        globals: &wgpu::BindGroup,
+        source: &wgpu::Texture,
+        region: Bounds<DevicePixels>,
+        passes: u32,
+    ) {
+        let origin = wgpu::Origin3d {
+            x: region.origin.x.0 as u32,
+            y: region.origin.y.0 as u32,
+            z: 0,
+        };
+        encoder.copy_texture_to_texture(
+            wgpu::ImageCopyTexture {
+                texture: source,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyTexture {
+                texture: &self.levels[0].texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width: region.size.width.0 as u32,
+                height: region.size.height.0 as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let passes = passes as usize;
+        let down = (1..=passes).map(|level| (&variant.blur_down, level - 1, level));
+        let up = (1..passes)
+            .rev()
+            .map(|level| (&variant.blur_up, level + 1, level));
+        for (pipeline, source_level, target_level) in down.chain(up) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("blur pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.levels[target_level].view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, globals, &[]);
+            render_pass.set_bind_group(1, &self.levels[source_level].bind_group, &[]);
+            set_scissor_rect(&mut render_pass, &self.level_region(region, target_level));
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// `region` scaled down to `level`, rounded out and kept within its texture.
+    fn level_region(&self, region: Bounds<DevicePixels>, level: usize) -> Bounds<DevicePixels> {
+        let scale_down = |value: DevicePixels, round_up: bool| {
+            let rounding = if round_up { (1 << level) - 1 } else { 0 };
+            DevicePixels((value.0 + rounding) >> level)
+        };
+        let size = self.levels[level].texture.size();
+        let texture_bounds = Bounds::from_corners(
+            Point::default(),
+            Point {
+                x: DevicePixels(size.width as i32),
+                y: DevicePixels(size.height as i32),
+            },
+        );
+        Bounds::from_corners(
+            Point {
+                x: scale_down(region.origin.x, false),
+                y: scale_down(region.origin.y, false),
+            },
+            Point {
+                x: scale_down(region.right(), true),
+                y: scale_down(region.bottom(), true),
+            },
+        )
+        .intersect(&texture_bounds)
+    }
+}
@@ -1,36 +1,220 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use anyhow::Context as _;
 use pollster::block_on;
 
 pub(crate) struct WgpuContext {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
+    pub adapter_info: wgpu::AdapterInfo,
+    lost: Arc<AtomicBool>,
+    builder: WgpuContextBuilder,
 }
 
 impl WgpuContext {
-    pub fn new() -> Self {
-        let (device, queue) = block_on(Self::setup());
-        Self { device, queue }
+    pub fn builder() -> WgpuContextBuilder {
+        WgpuContextBuilder::default()
     }
 
-    async fn setup() -> (wgpu::Device, wgpu::Queue) {
-        let instance = wgpu::Instance::default();
+    /// Returns true once the device is lost, e.g. after a GPU reset, and every
+    /// resource created from it has become unusable.
+    pub fn is_lost(&self) -> bool {
+        // The lost callback only runs while the device is being polled.
+        self.device.poll(wgpu::Maintain::Poll);
+        self.lost.load(Ordering::Acquire)
+    }
 
-        let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, None)
-            .await
-            .expect("No suitable GPU adapters found on the system!");
+    /// Creates a new context with the options this one was built with, to
+    /// replace it after the device was lost.
+    pub fn recreate(&self) -> anyhow::Result<Self> {
+        self.builder.clone().build()
+    }
+}
+
+/// Chooses the adapter and device a [`WgpuContext`] is created with.
+///
+/// Defaults follow wgpu's environment variables (`WGPU_BACKEND`,
+/// `WGPU_POWER_PREF`, `WGPU_ADAPTER_NAME`), falling back to every backend and
+/// the default power preference.
+#[derive(Clone, Debug)]
+pub(crate) struct WgpuContextBuilder {
+    backends: wgpu::Backends,
+    power_preference: wgpu::PowerPreference,
+    force_fallback_adapter: bool,
+    optional_features: wgpu::Features,
+    /// Lowercase part of the name of the adapter to pick over the power
+    /// preference, unless a fallback adapter is forced.
+    adapter_name: Option<String>,
+}
+
+impl Default for WgpuContextBuilder {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
+            power_preference: wgpu::util::power_preference_from_env().unwrap_or_default(),
+            force_fallback_adapter: false,
+            // Only used to speed up startup, so it's fine to go without.
+            optional_features: wgpu::Features::PIPELINE_CACHE,
+            adapter_name: std::env::var("WGPU_ADAPTER_NAME")
+                .ok()
+                .map(|name| name.to_lowercase()),
+        }
+    }
+}
+
+impl WgpuContextBuilder {
+    /// Restricts the backends adapters are picked from, e.g. Vulkan or GL.
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// Only accept a fallback (software) adapter, such as llvmpipe. Useful for
+    /// tests that need deterministic output, or hardware that misbehaves.
+    pub fn force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    /// Features to enable when the adapter supports them, replacing the
+    /// default of [`wgpu::Features::PIPELINE_CACHE`]. Check `device.features()`
+    /// to see which ones were actually enabled.
+    pub fn optional_features(mut self, features: wgpu::Features) -> Self {
+        self.optional_features = features;
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<WgpuContext> {
+        block_on(self.build_async())
+    }
+
+    async fn build_async(self) -> anyhow::Result<WgpuContext> {
+        let builder = self.clone();
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        });
+
+        let adapter = match &self.adapter_name {
+            // Like `wgpu::util::initialize_adapter_from_env`, which panics instead.
+            Some(name) if !self.force_fallback_adapter => instance
+                .enumerate_adapters(self.backends)
+                .into_iter()
+                .find(|adapter| adapter.get_info().name.to_lowercase().contains(name))
+                .with_context(|| {
+                    format!(
+                        "no GPU adapter named like {name:?} found (backends: {:?})",
+                        self.backends
+                    )
+                })?,
+            _ => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: self.power_preference,
+                    force_fallback_adapter: self.force_fallback_adapter,
+                    compatible_surface: None,
+                })
+                .await
+                .with_context(|| {
+                    format!(
+                        "no suitable GPU adapter found (backends: {:?}, fallback only: {})",
+                        self.backends, self.force_fallback_adapter
+                    )
+                })?,
+        };
         let adapter_info = adapter.get_info();
-        println!("Using {} ({:?})", adapter_info.name, adapter_info.backend);
 
-        adapter
+        let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    required_features: wgpu::Features::default(),
+                    required_features: adapter.features() & self.optional_features,
                     required_limits: wgpu::Limits::default(),
                     memory_hints: wgpu::MemoryHints::Performance,
                 },
                 None,
             )
             .await
-            .expect("Unable to find a suitable GPU adapter!")
+            .with_context(|| {
+                format!(
+                    "failed to create a device on {} ({:?})",
+                    adapter_info.name, adapter_info.backend
+                )
+            })?;
+
+        let lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let lost = lost.clone();
+            move |_, _| lost.store(true, Ordering::Release)
+        });
+
+        Ok(WgpuContext {
+            device,
+            queue,
+            adapter_info,
+            lost,
+            builder,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_force_fallback_adapter_picks_a_software_adapter() {
+        let gpu_ctx = WgpuContext::builder()
+            .force_fallback_adapter(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(gpu_ctx.adapter_info.device_type, wgpu::DeviceType::Cpu);
+    }
+
+    #[test]
+    fn test_adapter_name_picks_the_adapter() {
+        let fallback = WgpuContext::builder()
+            .force_fallback_adapter(true)
+            .build()
+            .unwrap();
+        let name = fallback.adapter_info.name.to_lowercase();
+
+        let gpu_ctx = WgpuContextBuilder {
+            adapter_name: Some(name.clone()),
+            ..WgpuContext::builder()
+        }
+        .build()
+        .unwrap();
+        assert_eq!(gpu_ctx.adapter_info.name.to_lowercase(), name);
+
+        let missing = WgpuContextBuilder {
+            adapter_name: Some("no such adapter".to_string()),
+            ..WgpuContext::builder()
+        };
+        assert!(missing.build().is_err());
+    }
+
+    #[test]
+    fn test_optional_features_replace_the_default() {
+        let builder = WgpuContext::builder();
+        assert_eq!(builder.optional_features, wgpu::Features::PIPELINE_CACHE);
+
+        let gpu_ctx = builder
+            .optional_features(wgpu::Features::empty())
+            .force_fallback_adapter(true)
+            .build()
+            .unwrap();
+        assert!(!gpu_ctx
+            .device
+            .features()
+            .contains(wgpu::Features::PIPELINE_CACHE));
     }
 }
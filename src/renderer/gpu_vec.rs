@@ -1,7 +1,9 @@
 // core code copy from https://github.com/audulus/vger-rs/blob/main/src/gpu_vec.rs
 
 use std::mem::size_of;
-use std::ops::Index;
+use std::ops::{Index, Range};
+
+use super::upload_belt::UploadBelt;
 
 pub(crate) const INIT_CAPACITY: usize = 1024;
 
@@ -45,28 +47,64 @@ impl<T: Copy> GPUVec<T> {
         }
     }
 
-    /// Updates the underlying gpu buffer with self.data.
-    ///
-    /// We'd like to write directly to the mapped buffer, but that seemed
-    /// tricky with wgpu.
+    /// Replaces the contents with `data` and writes it through the queue right
+    /// away. Meant for small, fixed size buffers such as uniforms.
     pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[T]) -> bool {
-        self.data = data.to_vec();
+        self.clear();
+        self.push(data);
+        let realloc = self.reserve(device);
+        if !self.data.is_empty() {
+            queue.write_buffer(&self.buffer, 0, self.bytes());
+        }
+        realloc
+    }
+
+    /// Appends the instances of one batch to this frame's data and returns
+    /// their range, to be drawn with it as `first_instance`.
+    pub fn push(&mut self, data: &[T]) -> Range<u32> {
+        let start = self.data.len() as u32;
+        self.data.extend_from_slice(data);
+        start..self.data.len() as u32
+    }
+
+    /// Like [`GPUVec::push`], for instances that are built while queueing them.
+    pub fn extend(&mut self, data: impl IntoIterator<Item = T>) -> Range<u32> {
+        let start = self.data.len() as u32;
+        self.data.extend(data);
+        start..self.data.len() as u32
+    }
+
+    /// Records an upload of everything pushed this frame through `belt`, growing
+    /// the buffer at most once. Returns true if the buffer was reallocated, in
+    /// which case bind groups referencing it must be recreated.
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        belt: &mut UploadBelt,
+    ) -> bool {
+        let realloc = self.reserve(device);
+        belt.write(device, encoder, &self.buffer, 0, self.bytes());
+        realloc
+    }
+
+    fn reserve(&mut self, device: &wgpu::Device) -> bool {
         let realloc = self.data.len() > self.capacity;
         if realloc {
             self.capacity = self.data.len().next_power_of_two();
             self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
                 label: Some(self.label.as_str()),
                 size: (size_of::<T>() * self.capacity) as u64,
-                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                usage: self.buffer.usage(),
                 mapped_at_creation: false,
             });
         }
+        realloc
+    }
 
+    fn bytes(&self) -> &[u8] {
         let sz = self.data.len() * size_of::<T>();
-        queue.write_buffer(&self.buffer, 0, unsafe {
-            std::slice::from_raw_parts_mut(self.data[..].as_ptr() as *mut u8, sz)
-        });
-        realloc
+        unsafe { std::slice::from_raw_parts(self.data[..].as_ptr() as *const u8, sz) }
     }
 
     pub fn bind_group_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
@@ -82,7 +120,7 @@ impl<T: Copy> GPUVec<T> {
         }
     }
 
-    pub fn bind_group_entry(&self, binding: u32) -> wgpu::BindGroupEntry {
+    pub fn bind_group_entry(&self, binding: u32) -> wgpu::BindGroupEntry<'_> {
         wgpu::BindGroupEntry {
             binding,
             resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
@@ -93,13 +131,10 @@ impl<T: Copy> GPUVec<T> {
         }
     }
 
+    /// Drops the data pushed for the previous frame, keeping the buffer.
     pub fn clear(&mut self) {
         self.data.clear();
     }
-
-    pub fn len(&self) -> usize {
-        self.data.len()
-    }
 }
 
 impl<T: Copy> Index<usize> for GPUVec<T> {
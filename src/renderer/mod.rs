@@ -1,14 +1,33 @@
-use std::{fs::File, sync::Arc};
+use std::{fs::File, ops::Range, path::PathBuf, sync::Arc};
 
+use blur::BlurChain;
 use context::WgpuContext;
 use futures::executor::block_on;
-use pipelines::Pipelines;
+use pipelines::{GradientStops, PipelineConfig, Pipelines};
+use shaders::{ShaderFiles, ShaderWatcher, Shaders};
+use texture_pool::TexturePool;
+use upload_belt::UploadBelt;
 
-use crate::scene::{PrimitiveBatch, Scene};
+use crate::{
+    color::{BackgroundTag, ColorSpace, Hsla, Rgba},
+    geometry::{
+        Bounds, Corners, DevicePixels, Edges, Point, ScaledPixels, Size, TransformationMatrix,
+    },
+    scene::{BorderStyle, DrawOrder, PrimitiveBatch, Quad, Scene},
+};
 
+mod blur;
 pub mod context;
 mod gpu_vec;
+mod pipeline_cache;
 mod pipelines;
+pub mod shaders;
+mod texture_pool;
+mod upload_belt;
+mod wgsl_layout;
+
+pub use shaders::ShaderError;
+pub use upload_belt::UploadStats;
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -18,86 +37,1024 @@ struct GlobalParams {
     pub pad: u32, // align to 8 bytes
 }
 
+/// A path vertex as uploaded, with its path's transformation, so a batch of
+/// paths is still drawn with one call.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TransformedPathVertex {
+    xy_position: Point<ScaledPixels>,
+    color: Hsla,
+    transformation: TransformationMatrix,
+}
+
+/// A quad as uploaded. Its gradient stops go to a buffer of their own, so
+/// there can be any number of them.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct QuadInstance {
+    order: DrawOrder,
+    border_style: BorderStyle,
+    bounds: Bounds<ScaledPixels>,
+    background: BackgroundInstance,
+    border_colors: Edges<Hsla>,
+    corner_radii: Corners<Size<ScaledPixels>>,
+    border_widths: Edges<ScaledPixels>,
+    border_dash_length: ScaledPixels,
+    border_dash_gap: ScaledPixels,
+    transformation: TransformationMatrix,
+}
+
+/// A [`Background`](crate::color::Background) as uploaded, with the range of
+/// its stops in the frame's gradient stop buffer.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BackgroundInstance {
+    tag: BackgroundTag,
+    color_space: ColorSpace,
+    angle: f32,
+    stop_offset: u32,
+    center: Point<f32>,
+    stop_count: u32,
+    solid: Hsla,
+    pad: u32, // align to 8 bytes
+}
+
+impl QuadInstance {
+    /// Pushes the quad's gradient stops, if any, along the way.
+    fn new(quad: &Quad, gradient_stops: &mut GradientStops) -> Self {
+        let background = &quad.background;
+        let stops = gradient_stops.push(&background.stops);
+        Self {
+            order: quad.order,
+            border_style: quad.border_style,
+            bounds: quad.bounds,
+            background: BackgroundInstance {
+                tag: background.tag,
+                color_space: background.color_space,
+                angle: background.angle,
+                stop_offset: stops.start,
+                center: background.center,
+                stop_count: stops.len() as u32,
+                solid: background.solid,
+                pad: 0,
+            },
+            border_colors: quad.border_colors,
+            corner_radii: quad.corner_radii,
+            border_widths: quad.border_widths,
+            border_dash_length: quad.border_dash_length,
+            border_dash_gap: quad.border_dash_gap,
+            transformation: quad.transformation,
+        }
+    }
+}
+
+/// Draws an opacity layer's texture over the target it was pushed on.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LayerComposite {
+    bounds: Bounds<ScaledPixels>,
+    opacity: f32,
+    pad: u32, // align to 8 bytes
+}
+
+const DEFAULT_CLEAR_COLOR: Hsla = Hsla::black();
+
+/// Past this many disjoint damage rects they're merged into one, as the per-rect
+/// draw calls would cost more than the fill rate they save.
+const MAX_DAMAGE_RECTS: usize = 8;
+
+/// How the edges of primitives are anti-aliased.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Antialiasing {
+    /// Only the coverage each shader computes itself, such as the quad SDF.
+    /// Paths have none, so scenes containing any are drawn with
+    /// [`Antialiasing::Msaa4x`] regardless.
+    #[default]
+    Analytic,
+    /// Also render to a 4x multisampled target resolved every frame, for
+    /// primitives whose edges have no analytic coverage. Quads keep their SDF,
+    /// which MSAA only refines where the instance rect cuts through a pixel.
+    Msaa4x,
+}
+
+impl Antialiasing {
+    /// The mode `scene` is actually drawn with.
+    fn for_scene(self, scene: &Scene) -> Self {
+        if scene.paths.is_empty() {
+            self
+        } else {
+            Antialiasing::Msaa4x
+        }
+    }
+
+    fn sample_count(self) -> u32 {
+        match self {
+            Antialiasing::Analytic => 1,
+            Antialiasing::Msaa4x => 4,
+        }
+    }
+}
+
+/// Picks the alpha mode to configure a surface with, from the modes in its
+/// capabilities (`wgpu::SurfaceCapabilities::alpha_modes`), which are never
+/// empty.
+///
+/// Translucent surfaces, e.g. for a bar, need premultiplied output as Wayland
+/// composites that way; straight alpha is only a fallback, and darkens
+/// translucent edges drawn over transparent areas.
+pub fn choose_alpha_mode(
+    supported: &[wgpu::CompositeAlphaMode],
+    transparent: bool,
+) -> wgpu::CompositeAlphaMode {
+    let preferred: &[_] = if transparent {
+        &[
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+            wgpu::CompositeAlphaMode::Inherit,
+        ]
+    } else {
+        &[wgpu::CompositeAlphaMode::Opaque]
+    };
+    preferred
+        .iter()
+        .find(|mode| supported.contains(mode))
+        .copied()
+        .unwrap_or(supported[0])
+}
+
+/// A batch whose instances have been queued for upload, referenced by their
+/// range in the pipeline's instance buffer.
+enum DrawCall {
+    Quads(Range<u32>),
+    /// A range of vertices rather than instances.
+    Paths(Range<u32>),
+    Underlines(Range<u32>),
+    /// One backdrop blur, which ends the render pass so what's been drawn so
+    /// far can be blurred. `reach` is the region it samples.
+    BackdropBlur {
+        instance: u32,
+        passes: u32,
+        bounds: Bounds<DevicePixels>,
+        reach: Bounds<DevicePixels>,
+    },
+    /// Ends the render pass and continues in a texture from the pool, drawing
+    /// only within `clip`, until the matching pop composites it.
+    PushOpacityLayer {
+        instance: u32,
+        clip: Vec<Bounds<DevicePixels>>,
+    },
+    PopOpacityLayer,
+}
+
 pub struct Renderer {
     gpu_ctx: Arc<WgpuContext>,
     pipelines: Pipelines,
+    texture_desc: wgpu::TextureDescriptor<'static>,
+    // Kept across frames so undamaged regions can be reused. Shared with the
+    // frame's `RenderTarget`, which `render` needs alongside `&mut self`.
+    render_texture: Arc<wgpu::Texture>,
+    antialiasing: Antialiasing,
+    alpha_mode: wgpu::CompositeAlphaMode,
+    clear_color: Hsla,
+    /// Rendered into and resolved to `render_texture` when using MSAA. Also kept
+    /// across frames, as partial redraws load its samples.
+    msaa_texture: Option<wgpu::Texture>,
+    /// Created for the first scene with a backdrop blur.
+    blur_chain: Option<BlurChain>,
+    texture_pool: TexturePool,
+    previous_scene: Option<Scene>,
+    upload_belt: UploadBelt,
+    shader_files: ShaderFiles,
+    shader_defines: Vec<String>,
+    /// The shaders the current pipelines were built from, reused to rebuild
+    /// them after a device loss.
+    shaders: Shaders,
+    shader_watcher: Option<ShaderWatcher>,
+    shader_error: Option<ShaderError>,
+    recovery_error: Option<anyhow::Error>,
 }
 
 impl Renderer {
     pub fn new(gpu_ctx: Arc<WgpuContext>) -> Self {
-        let pipelines = Pipelines::new(&gpu_ctx);
-
-        Self { gpu_ctx, pipelines }
-    }
-
-    // TODO: render_pass should generate every frame instead of being passed in
-    pub fn draw(&mut self, scene: &Scene) {
-        let device = &self.gpu_ctx.device;
-        let queue = &self.gpu_ctx.queue;
-        let texture_size = wgpu::Extent3d {
-            width: 512,
-            height: 512,
-            depth_or_array_layers: 1,
-        };
+        let shader_files = ShaderFiles::Embedded;
+        // The embedded shaders are already validated by build.rs.
+        let shaders = Shaders::compile(&shader_files, &[]).unwrap();
+        let antialiasing = Antialiasing::default();
+        let alpha_mode = wgpu::CompositeAlphaMode::Opaque;
 
         let texture_desc = wgpu::TextureDescriptor {
-            size: texture_size,
+            size: wgpu::Extent3d {
+                width: 512,
+                height: 512,
+                depth_or_array_layers: 1,
+            },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
             label: None,
-            view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+            view_formats: &[],
         };
+        let mut pipelines = Pipelines::new(&gpu_ctx, &shaders);
+        pipelines.prepare(
+            &gpu_ctx.device,
+            pipeline_config(texture_desc.format, antialiasing, alpha_mode),
+        );
+        let render_texture = Arc::new(gpu_ctx.device.create_texture(&texture_desc));
+        let msaa_texture = create_msaa_texture(&gpu_ctx.device, &texture_desc, antialiasing);
 
-        let render_texture = device.create_texture(&texture_desc);
+        Self {
+            gpu_ctx,
+            pipelines,
+            texture_desc,
+            render_texture,
+            antialiasing,
+            alpha_mode,
+            clear_color: DEFAULT_CLEAR_COLOR,
+            msaa_texture,
+            blur_chain: None,
+            texture_pool: TexturePool::default(),
+            previous_scene: None,
+            upload_belt: UploadBelt::new(),
+            shader_files,
+            shader_defines: Vec::new(),
+            shaders,
+            shader_watcher: None,
+            shader_error: None,
+            recovery_error: None,
+        }
+    }
 
-        let view = render_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    /// Dev mode: reloads the WGSL files in `dir` whenever one of them changes,
+    /// checked at the start of every [`Renderer::draw`]. Shaders that fail to
+    /// compile keep the previous pipelines, with the error available from
+    /// [`Renderer::shader_error`].
+    pub fn watch_shaders(&mut self, dir: impl Into<PathBuf>) {
+        let watcher = ShaderWatcher::new(dir.into());
+        self.shader_files = ShaderFiles::Directory(watcher.dir().to_path_buf());
+        self.shader_watcher = Some(watcher);
+    }
 
-        let pass_descriptor = wgpu::RenderPassDescriptor {
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            ..Default::default()
+    /// Recompiles every pipeline with the given defines, e.g. to toggle debug
+    /// visualizations such as [`shaders::DEBUG_QUAD_BOUNDS`]. On error the
+    /// previous pipelines are kept.
+    pub fn set_shader_defines(&mut self, defines: &[&str]) -> Result<(), ShaderError> {
+        self.shader_defines = defines.iter().map(|define| define.to_string()).collect();
+        self.rebuild_pipelines()
+    }
+
+    /// Switches anti-aliasing modes. The next frame is redrawn in full.
+    pub fn set_antialiasing(&mut self, antialiasing: Antialiasing) {
+        if self.antialiasing == antialiasing {
+            return;
+        }
+        self.antialiasing = antialiasing;
+        self.msaa_texture =
+            create_msaa_texture(&self.gpu_ctx.device, &self.texture_desc, antialiasing);
+        self.previous_scene = None;
+    }
+
+    pub fn antialiasing(&self) -> Antialiasing {
+        self.antialiasing
+    }
+
+    /// Renders in the format the surface was configured with, picking how
+    /// shaders encode colors for it. Defaults to `Rgba8UnormSrgb`.
+    pub fn set_format(&mut self, format: wgpu::TextureFormat) {
+        if self.texture_desc.format == format {
+            return;
+        }
+        self.texture_desc.format = format;
+        let device = &self.gpu_ctx.device;
+        self.render_texture = Arc::new(device.create_texture(&self.texture_desc));
+        self.msaa_texture = create_msaa_texture(device, &self.texture_desc, self.antialiasing);
+        self.previous_scene = None;
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.texture_desc.format
+    }
+
+    /// Matches the output to the alpha mode the surface was configured with,
+    /// usually picked by [`choose_alpha_mode`]. Defaults to opaque.
+    pub fn set_alpha_mode(&mut self, alpha_mode: wgpu::CompositeAlphaMode) {
+        if self.alpha_mode != alpha_mode {
+            self.alpha_mode = alpha_mode;
+            self.previous_scene = None;
+        }
+    }
+
+    pub fn alpha_mode(&self) -> wgpu::CompositeAlphaMode {
+        self.alpha_mode
+    }
+
+    /// The color behind everything, black by default. Use a transparent color
+    /// for translucent surfaces, with an alpha mode that isn't opaque.
+    pub fn set_clear_color(&mut self, clear_color: Hsla) {
+        if self.clear_color != clear_color {
+            self.clear_color = clear_color;
+            self.previous_scene = None;
+        }
+    }
+
+    fn pipeline_config(&self) -> PipelineConfig {
+        pipeline_config(self.texture_desc.format, self.antialiasing, self.alpha_mode)
+    }
+
+    /// The clear color as a target with `config` expects it: linear unless
+    /// it's written without encoding, and premultiplied if the pipelines are.
+    fn wgpu_clear_color(&self, config: &PipelineConfig) -> wgpu::Color {
+        let Rgba { r, g, b, a } = self.clear_color.into();
+        let multiplier = if config.premultiplied_alpha { a } else { 1.0 };
+        let channel = |value: f32| {
+            let value = if config.encode_srgb() {
+                value
+            } else {
+                srgb_to_linear(value)
+            };
+            (value * multiplier) as f64
         };
+        wgpu::Color {
+            r: channel(r),
+            g: channel(g),
+            b: channel(b),
+            a: a as f64,
+        }
+    }
+
+    /// The error from the last failed shader reload, if it hasn't been fixed since.
+    pub fn shader_error(&self) -> Option<&ShaderError> {
+        self.shader_error.as_ref()
+    }
+
+    fn reload_shaders(&mut self) {
+        if self
+            .shader_watcher
+            .as_mut()
+            .is_some_and(|watcher| watcher.poll())
+        {
+            self.rebuild_pipelines().ok();
+        }
+    }
+
+    fn rebuild_pipelines(&mut self) -> Result<(), ShaderError> {
+        let defines = self
+            .shader_defines
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let result = Shaders::compile(&self.shader_files, &defines).and_then(|shaders| {
+            // naga accepts features the device may lack, so wgpu gets the final say.
+            let mut pipelines = Pipelines::new(&self.gpu_ctx, &shaders);
+            match pipelines.try_prepare(&self.gpu_ctx.device, self.pipeline_config()) {
+                Ok(()) => Ok((shaders, pipelines)),
+                Err(error) => Err(ShaderError {
+                    path: self.shader_files.path(&error.entry),
+                    location: None,
+                    diagnostic: error.source.to_string(),
+                }),
+            }
+        });
+
+        match result {
+            Ok((shaders, pipelines)) => {
+                self.shaders = shaders;
+                self.pipelines = pipelines;
+                self.shader_error = None;
+                // Everything on screen was drawn with the old shaders.
+                self.previous_scene = None;
+                Ok(())
+            }
+            Err(error) => {
+                self.shader_error = Some(error.clone());
+                Err(error)
+            }
+        }
+    }
+
+    /// Why no new device could be created after the last one was lost, if
+    /// that's still the case.
+    pub fn recovery_error(&self) -> Option<&anyhow::Error> {
+        self.recovery_error.as_ref()
+    }
+
+    /// What the last drawn frame uploaded to the GPU.
+    pub fn upload_stats(&self) -> UploadStats {
+        self.upload_belt.stats()
+    }
+
+    /// The context currently rendered with. It is replaced when the device is
+    /// lost, so don't hold on to it across frames.
+    pub fn gpu_ctx(&self) -> &Arc<WgpuContext> {
+        &self.gpu_ctx
+    }
+
+    /// Renders the parts of `scene` that changed since the last call and returns
+    /// the damaged regions, ready to be passed to `wl_surface.damage_buffer`.
+    ///
+    /// An empty result means the scene didn't change and nothing was submitted.
+    ///
+    /// If the device was lost, a new one is created along with everything
+    /// rendered with it, and the whole scene is drawn again. Should that fail
+    /// too, nothing is drawn, the reason is kept in
+    /// [`Renderer::recovery_error`] and recovery is retried on the next call.
+    // TODO: render_pass should generate every frame instead of being passed in
+    pub fn draw(&mut self, scene: &Scene) -> Vec<Bounds<DevicePixels>> {
+        self.reload_shaders();
+
+        if self.gpu_ctx.is_lost() && !self.recover() {
+            return Vec::new();
+        }
+
+        let device = &self.gpu_ctx.device;
+        device.push_error_scope(wgpu::ErrorFilter::Internal);
+        device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let damage = self.draw_frame(scene);
+        let device = &self.gpu_ctx.device;
+        let errors = [
+            block_on(device.pop_error_scope()),
+            block_on(device.pop_error_scope()),
+            block_on(device.pop_error_scope()),
+        ];
+
+        let Some(error) = errors.into_iter().flatten().next() else {
+            return damage;
+        };
+        if !self.gpu_ctx.is_lost() {
+            // A bug rather than a lost device; fail as loudly as wgpu would.
+            panic!("{error}");
+        }
+        if self.recover() {
+            self.draw_frame(scene)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Replaces the lost context with a new one and recreates every resource
+    /// that belonged to the old device. Returns false if no new device could
+    /// be created, keeping the error for [`Renderer::recovery_error`].
+    ///
+    /// There are no atlases or surfaces yet; they will need recreating here too.
+    fn recover(&mut self) -> bool {
+        let gpu_ctx = match self.gpu_ctx.recreate() {
+            Ok(gpu_ctx) => Arc::new(gpu_ctx),
+            Err(error) => {
+                self.recovery_error = Some(error);
+                return false;
+            }
+        };
+        self.recovery_error = None;
+
+        // Not recompiled: a half-edited shader in dev mode shouldn't keep us
+        // from drawing.
+        self.pipelines = Pipelines::new(&gpu_ctx, &self.shaders);
+        self.render_texture = Arc::new(gpu_ctx.device.create_texture(&self.texture_desc));
+        self.msaa_texture =
+            create_msaa_texture(&gpu_ctx.device, &self.texture_desc, self.antialiasing);
+        self.blur_chain = None;
+        self.texture_pool = TexturePool::default();
+        self.upload_belt = UploadBelt::new();
+        self.previous_scene = None;
+        self.gpu_ctx = gpu_ctx;
+        true
+    }
+
+    /// Saves the last rendered frame as a PNG, for debugging.
+    // TODO: delete this when we have a window
+    pub fn save_png(&self, path: &str) {
+        save(
+            &self.gpu_ctx.device,
+            &self.gpu_ctx.queue,
+            &self.texture_desc,
+            &self.render_texture,
+            path,
+        );
+    }
+
+    /// Reads back the last rendered frame as tightly packed rows, in the
+    /// [format](Self::format) it was rendered in.
+    #[cfg(test)]
+    pub fn read_pixels(&self) -> Vec<u8> {
+        read_texture(&self.gpu_ctx, &self.texture_desc, &self.render_texture)
+    }
+
+    /// Renders all of `scene` into a new texture of `format` and reads it back
+    /// like [`Renderer::read_pixels`], e.g. for screenshots. The surface and
+    /// its damage tracking are left alone.
+    #[cfg(test)]
+    pub fn capture(&mut self, scene: &Scene, format: wgpu::TextureFormat) -> Vec<u8> {
+        let texture_desc = wgpu::TextureDescriptor {
+            label: Some("capture texture"),
+            format,
+            ..self.texture_desc.clone()
+        };
+        let antialiasing = self.antialiasing.for_scene(scene);
+        let device = &self.gpu_ctx.device;
+        let texture = device.create_texture(&texture_desc);
+        let msaa_texture = create_msaa_texture(device, &texture_desc, antialiasing);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = msaa_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let target = RenderTarget {
+            view: msaa_view.as_ref().unwrap_or(&view),
+            resolve_target: msaa_view.as_ref().map(|_| &view),
+            texture: &texture,
+            size: texture_desc.size,
+            config: pipeline_config(format, antialiasing, self.alpha_mode),
+        };
+        self.render(scene, &target, &[self.viewport()], true);
+
+        read_texture(&self.gpu_ctx, &texture_desc, &texture)
+    }
+
+    fn viewport(&self) -> Bounds<DevicePixels> {
+        Bounds {
+            origin: Point::default(),
+            size: Size {
+                width: DevicePixels(self.texture_desc.size.width as i32),
+                height: DevicePixels(self.texture_desc.size.height as i32),
+            },
+        }
+    }
+
+    fn draw_frame(&mut self, scene: &Scene) -> Vec<Bounds<DevicePixels>> {
+        let antialiasing = self.antialiasing.for_scene(scene);
+        if self.msaa_texture.is_some() != (antialiasing.sample_count() > 1) {
+            // The samples of the previous frame can't be loaded at another count.
+            self.msaa_texture =
+                create_msaa_texture(&self.gpu_ctx.device, &self.texture_desc, antialiasing);
+            self.previous_scene = None;
+        }
+
+        let viewport = self.viewport();
+        let damage = match &self.previous_scene {
+            Some(previous) => merge_damage(
+                scene
+                    .damage_since(previous)
+                    .iter()
+                    .map(|bounds| bounds.round_out().intersect(&viewport))
+                    .filter(|bounds| !bounds.is_empty())
+                    .collect(),
+            ),
+            None => vec![viewport],
+        };
+        if damage.is_empty() {
+            return damage;
+        }
+
+        let texture = self.render_texture.clone();
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = self
+            .msaa_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let target = RenderTarget {
+            view: msaa_view.as_ref().unwrap_or(&view),
+            resolve_target: msaa_view.as_ref().map(|_| &view),
+            texture: &texture,
+            size: self.texture_desc.size,
+            config: pipeline_config(self.texture_desc.format, antialiasing, self.alpha_mode),
+        };
+        self.render(scene, &target, &damage, damage == [viewport]);
+
+        self.previous_scene = Some(scene.clone());
+        damage
+    }
+
+    /// Draws `scene` into `damage`, which is cleared first. On a full redraw
+    /// the target is cleared as it's loaded instead.
+    fn render(
+        &mut self,
+        scene: &Scene,
+        target: &RenderTarget,
+        damage: &[Bounds<DevicePixels>],
+        full_redraw: bool,
+    ) {
+        let device = &self.gpu_ctx.device;
+        let queue = &self.gpu_ctx.queue;
+        let clear_color = self.wgpu_clear_color(&target.config);
+        self.pipelines.prepare(device, target.config);
 
         let global_params = GlobalParams {
-            viewport_size: [texture_size.width as f32, texture_size.height as f32],
-            premultiplied_alpha: 0,
+            viewport_size: [target.size.width as f32, target.size.height as f32],
+            premultiplied_alpha: target.config.premultiplied_alpha as u32,
             pad: 0,
         };
+        let target_bounds = Bounds {
+            origin: Point::default(),
+            size: Size {
+                width: DevicePixels(target.size.width as i32),
+                height: DevicePixels(target.size.height as i32),
+            },
+        };
 
-        let mut command_encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        if !scene.backdrop_blurs.is_empty()
+            && !self
+                .blur_chain
+                .as_ref()
+                .is_some_and(|chain| chain.fits(target.config.format, target.size))
         {
-            let mut render_pass = command_encoder.begin_render_pass(&pass_descriptor);
+            self.blur_chain = Some(BlurChain::new(
+                device,
+                &self.pipelines,
+                target.config.format,
+                target.size,
+            ));
+        }
 
-            for batch in scene.batches() {
+        // Every batch is appended to its pipeline's instance buffer first, so a
+        // frame costs one upload per primitive kind no matter how it's batched.
+        self.pipelines.quads.clear();
+        self.pipelines.gradient_stops.clear();
+        self.pipelines.paths.clear();
+        self.pipelines.underlines.clear();
+        self.pipelines.backdrop_blurs.clear();
+        self.pipelines.opacity_layers.clear();
+        let mut draw_calls = Vec::new();
+        // Where each open layer is drawn, innermost last.
+        let mut clips = vec![damage.to_vec()];
+        // Layers outside of the damage are skipped along with their contents.
+        let mut skipped_layers = 0;
+        for batch in scene.batches() {
+            if skipped_layers > 0 {
                 match batch {
-                    PrimitiveBatch::Quads(quads) => {
-                        let pipeline_ctx = &mut self.pipelines.quads;
+                    PrimitiveBatch::PushOpacityLayer(_) => skipped_layers += 1,
+                    PrimitiveBatch::PopOpacityLayer => skipped_layers -= 1,
+                    _ => {}
+                }
+                continue;
+            }
+            let clip = clips.last().unwrap();
+            match batch {
+                PrimitiveBatch::Quads(quads) => {
+                    let gradient_stops = &mut self.pipelines.gradient_stops;
+                    let instances = self.pipelines.quads.extend(
+                        quads
+                            .iter()
+                            .map(|quad| QuadInstance::new(quad, gradient_stops)),
+                    );
+                    draw_calls.push(DrawCall::Quads(instances));
+                }
+                // TODO: draw sprites once there's a glyph atlas to sample; they're
+                // skipped until then.
+                PrimitiveBatch::MonochromeSprites(_) => {}
+                PrimitiveBatch::Paths(paths) => {
+                    // Pushed back to back, so the whole batch is one range.
+                    let vertices = paths
+                        .iter()
+                        .map(|path| {
+                            self.pipelines
+                                .paths
+                                .extend(path.vertices.iter().map(|vertex| TransformedPathVertex {
+                                    xy_position: vertex.xy_position,
+                                    color: vertex.color,
+                                    transformation: path.transformation,
+                                }))
+                        })
+                        .reduce(|first, last| first.start..last.end)
+                        .unwrap();
+                    draw_calls.push(DrawCall::Paths(vertices));
+                }
+                PrimitiveBatch::Underlines(underlines) => {
+                    let instances = self.pipelines.underlines.push(underlines);
+                    draw_calls.push(DrawCall::Underlines(instances));
+                }
+                PrimitiveBatch::BackdropBlurs(backdrop_blurs) => {
+                    // Each one blurs the ones before it too, so they can't share a pass.
+                    let instances = self.pipelines.backdrop_blurs.push(backdrop_blurs);
+                    for (instance, backdrop_blur) in instances.zip(backdrop_blurs) {
+                        let bounds = backdrop_blur.bounds.round_out();
+                        if clip.iter().any(|rect| rect.intersects(&bounds)) {
+                            draw_calls.push(DrawCall::BackdropBlur {
+                                instance,
+                                passes: backdrop_blur.passes(),
+                                bounds,
+                                reach: backdrop_blur.reach().round_out().intersect(&target_bounds),
+                            });
+                        }
+                    }
+                }
+                PrimitiveBatch::PushOpacityLayer(layer) => {
+                    let bounds = layer.bounds.round_out();
+                    let clip = clip
+                        .iter()
+                        .map(|rect| rect.intersect(&bounds))
+                        .filter(|rect| !rect.is_empty())
+                        .collect::<Vec<_>>();
+                    if clip.is_empty() {
+                        skipped_layers += 1;
+                        continue;
+                    }
+                    let instance = self.pipelines.opacity_layers.push(&[LayerComposite {
+                        bounds: layer.bounds,
+                        opacity: layer.opacity,
+                        pad: 0,
+                    }]);
+                    clips.push(clip.clone());
+                    draw_calls.push(DrawCall::PushOpacityLayer {
+                        instance: instance.start,
+                        clip,
+                    });
+                }
+                PrimitiveBatch::PopOpacityLayer => {
+                    clips.pop();
+                    draw_calls.push(DrawCall::PopOpacityLayer);
+                }
+            }
+        }
+
+        let mut command_encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.upload_belt.begin_frame();
+        self.pipelines.globals.update(device, queue, global_params);
+        self.pipelines
+            .quads
+            .upload(device, &mut command_encoder, &mut self.upload_belt);
+        self.pipelines
+            .gradient_stops
+            .upload(device, &mut command_encoder, &mut self.upload_belt);
+        self.pipelines
+            .paths
+            .upload(device, &mut command_encoder, &mut self.upload_belt);
+        self.pipelines
+            .underlines
+            .upload(device, &mut command_encoder, &mut self.upload_belt);
+        self.pipelines
+            .backdrop_blurs
+            .upload(device, &mut command_encoder, &mut self.upload_belt);
+        self.pipelines
+            .opacity_layers
+            .upload(device, &mut command_encoder, &mut self.upload_belt);
+
+        // Draws go to the innermost open layer, or the target when there's none.
+        // Switching between them, or blurring what's been drawn so far, ends
+        // the render pass and starts a new one that loads what it stored.
+        let variant = self.pipelines.variant(&target.config);
+        let globals = &self.pipelines.globals.bind_group;
+        let mut layers: Vec<OpenLayer> = Vec::new();
+        let load = if full_redraw {
+            wgpu::LoadOp::Clear(clear_color)
+        } else {
+            wgpu::LoadOp::Load
+        };
+        let mut render_pass =
+            begin_render_pass(&mut command_encoder, target, &layers, load, globals);
+        if !full_redraw {
+            render_pass.set_pipeline(&variant.clear);
+            render_pass.set_blend_constant(clear_color);
+            for rect in damage {
+                set_scissor_rect(&mut render_pass, rect);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
 
-                        pipeline_ctx.update(device, queue, global_params, quads);
+        for draw_call in draw_calls {
+            let clip = layers.last().map_or(damage, |layer| &layer.clip);
+            match draw_call {
+                DrawCall::Quads(instances) => {
+                    let pipeline_ctx = &self.pipelines.quads;
 
-                        render_pass.set_pipeline(&pipeline_ctx.pipeline);
+                    render_pass.set_pipeline(&variant.quads);
 
-                        render_pass.set_bind_group(0, &pipeline_ctx.bind_group, &[]);
+                    render_pass.set_bind_group(1, &pipeline_ctx.bind_group, &[]);
+                    render_pass.set_bind_group(2, &self.pipelines.gradient_stops.bind_group, &[]);
 
-                        render_pass.draw(0..4, 0..quads.len() as u32);
+                    for rect in clip {
+                        set_scissor_rect(&mut render_pass, rect);
+                        render_pass.draw(0..4, instances.clone());
                     }
                 }
+                DrawCall::Paths(vertices) => {
+                    render_pass.set_pipeline(&variant.paths);
+                    render_pass.set_bind_group(1, &self.pipelines.paths.bind_group, &[]);
+                    for rect in clip {
+                        set_scissor_rect(&mut render_pass, rect);
+                        render_pass.draw(vertices.clone(), 0..1);
+                    }
+                }
+                DrawCall::Underlines(instances) => {
+                    render_pass.set_pipeline(&variant.underlines);
+                    render_pass.set_bind_group(1, &self.pipelines.underlines.bind_group, &[]);
+                    for rect in clip {
+                        set_scissor_rect(&mut render_pass, rect);
+                        render_pass.draw(0..4, instances.clone());
+                    }
+                }
+                DrawCall::BackdropBlur {
+                    instance,
+                    passes,
+                    bounds,
+                    reach,
+                } => {
+                    // Once the pass has stored, and resolved, everything beneath
+                    // the blur. Within a layer, that's only what the layer drew.
+                    drop(render_pass);
+                    let blur_chain = self.blur_chain.as_ref().unwrap();
+                    let source = layers
+                        .last()
+                        .map_or(target.texture, |layer| &layer.texture.texture);
+                    blur_chain.blur(
+                        &mut command_encoder,
+                        variant,
+                        globals,
+                        source,
+                        reach,
+                        passes,
+                    );
+
+                    render_pass = begin_render_pass(
+                        &mut command_encoder,
+                        target,
+                        &layers,
+                        wgpu::LoadOp::Load,
+                        globals,
+                    );
+                    render_pass.set_pipeline(&variant.backdrop_blurs);
+                    render_pass.set_bind_group(1, &self.pipelines.backdrop_blurs.bind_group, &[]);
+                    render_pass.set_bind_group(2, blur_chain.blurred(), &[]);
+                    for rect in clip {
+                        let rect = rect.intersect(&bounds);
+                        if !rect.is_empty() {
+                            set_scissor_rect(&mut render_pass, &rect);
+                            render_pass.draw(0..4, instance..instance + 1);
+                        }
+                    }
+                }
+                DrawCall::PushOpacityLayer { instance, clip } => {
+                    drop(render_pass);
+                    let texture = self.texture_pool.acquire(
+                        device,
+                        &self.pipelines,
+                        target.config,
+                        target.size,
+                    );
+                    layers.push(OpenLayer {
+                        texture,
+                        instance,
+                        clip,
+                    });
+                    render_pass = begin_render_pass(
+                        &mut command_encoder,
+                        target,
+                        &layers,
+                        wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        globals,
+                    );
+                }
+                DrawCall::PopOpacityLayer => {
+                    drop(render_pass);
+                    let layer = layers.pop().unwrap();
+                    render_pass = begin_render_pass(
+                        &mut command_encoder,
+                        target,
+                        &layers,
+                        wgpu::LoadOp::Load,
+                        globals,
+                    );
+                    render_pass.set_pipeline(&variant.opacity_layers);
+                    render_pass.set_bind_group(1, &self.pipelines.opacity_layers.bind_group, &[]);
+                    render_pass.set_bind_group(2, &layer.texture.bind_group, &[]);
+                    for rect in &layer.clip {
+                        set_scissor_rect(&mut render_pass, rect);
+                        render_pass.draw(0..4, layer.instance..layer.instance + 1);
+                    }
+                    self.texture_pool.release(layer.texture);
+                }
             }
         }
+        drop(render_pass);
+        self.texture_pool.end_frame();
+
+        self.upload_belt.finish();
         queue.submit(Some(command_encoder.finish()));
+        self.upload_belt.recall();
+    }
+}
+
+/// Where [`Renderer::render`] draws to.
+struct RenderTarget<'a> {
+    view: &'a wgpu::TextureView,
+    /// Receives the resolved samples when `view` is multisampled.
+    resolve_target: Option<&'a wgpu::TextureView>,
+    /// The single sampled texture `view` or `resolve_target` belongs to,
+    /// which backdrop blurs outside of opacity layers copy from.
+    texture: &'a wgpu::Texture,
+    size: wgpu::Extent3d,
+    config: PipelineConfig,
+}
+
+/// An opacity layer being drawn into, composited with its `instance` of the
+/// opacity layer pipeline when it's popped.
+struct OpenLayer {
+    texture: texture_pool::PooledTexture,
+    instance: u32,
+    clip: Vec<Bounds<DevicePixels>>,
+}
+
+/// Begins a pass on the innermost of `layers`, or on `target` when there are
+/// none, with the globals bound. Its lifetime is detached from `encoder` so it
+/// can be replaced mid-frame; it must be dropped before `encoder` is used again.
+fn begin_render_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    target: &RenderTarget,
+    layers: &[OpenLayer],
+    load: wgpu::LoadOp<wgpu::Color>,
+    globals: &wgpu::BindGroup,
+) -> wgpu::RenderPass<'static> {
+    let (view, resolve_target) = match layers.last() {
+        Some(layer) => (layer.texture.render_view(), layer.texture.resolve_target()),
+        None => (target.view, target.resolve_target),
+    };
+    let mut render_pass = encoder
+        .begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        })
+        .forget_lifetime();
+    render_pass.set_bind_group(0, globals, &[]);
+    render_pass
+}
+
+fn pipeline_config(
+    format: wgpu::TextureFormat,
+    antialiasing: Antialiasing,
+    alpha_mode: wgpu::CompositeAlphaMode,
+) -> PipelineConfig {
+    PipelineConfig {
+        format,
+        sample_count: antialiasing.sample_count(),
+        // Opaque surfaces ignore alpha, so premultiplying is harmless there and
+        // keeps blending the alpha channel itself correct.
+        premultiplied_alpha: alpha_mode != wgpu::CompositeAlphaMode::PostMultiplied,
+    }
+}
+
+/// Same as `srgb_to_linear` in `common.wgsl`.
+fn srgb_to_linear(value: f32) -> f32 {
+    if value < 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn create_msaa_texture(
+    device: &wgpu::Device,
+    texture_desc: &wgpu::TextureDescriptor,
+    antialiasing: Antialiasing,
+) -> Option<wgpu::Texture> {
+    // 4x is supported for every surface format, so there's nothing to check.
+    let sample_count = antialiasing.sample_count();
+    (sample_count > 1).then(|| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("msaa texture"),
+            sample_count,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            ..texture_desc.clone()
+        })
+    })
+}
 
-        save(device, queue, &texture_desc, &render_texture);
+fn set_scissor_rect(render_pass: &mut wgpu::RenderPass, rect: &Bounds<DevicePixels>) {
+    render_pass.set_scissor_rect(
+        rect.origin.x.0 as u32,
+        rect.origin.y.0 as u32,
+        rect.size.width.0 as u32,
+        rect.size.height.0 as u32,
+    );
+}
+
+/// Merges overlapping damage rects, since drawing a primitive twice in the same
+/// frame would blend translucent pixels over themselves.
+fn merge_damage(mut rects: Vec<Bounds<DevicePixels>>) -> Vec<Bounds<DevicePixels>> {
+    let mut merged: Vec<Bounds<DevicePixels>> = Vec::new();
+    while let Some(mut rect) = rects.pop() {
+        while let Some(ix) = merged.iter().position(|other| other.intersects(&rect)) {
+            rect = rect.union(&merged.swap_remove(ix));
+        }
+        merged.push(rect);
     }
+
+    if merged.len() > MAX_DAMAGE_RECTS {
+        let union = merged
+            .iter()
+            .skip(1)
+            .fold(merged[0], |acc, rect| acc.union(rect));
+        merged = vec![union];
+    }
+    merged
 }
 
 // TODO: delete this when we have a window
@@ -106,12 +1063,42 @@ fn save(
     queue: &wgpu::Queue,
     descriptor: &wgpu::TextureDescriptor,
     texture: &wgpu::Texture,
+    path: &str,
 ) {
+    let output_buffer = copy_to_buffer(device, queue, descriptor, texture);
+    block_on(create_png(path, device, output_buffer, descriptor))
+}
+
+#[cfg(test)]
+fn read_texture(
+    gpu_ctx: &WgpuContext,
+    descriptor: &wgpu::TextureDescriptor,
+    texture: &wgpu::Texture,
+) -> Vec<u8> {
+    let device = &gpu_ctx.device;
+    let output_buffer = copy_to_buffer(device, &gpu_ctx.queue, descriptor, texture);
+    let buffer_slice = output_buffer.slice(..);
+    buffer_slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    let pixels = buffer_slice.get_mapped_range().to_vec();
+    output_buffer.unmap();
+    pixels
+}
+
+fn copy_to_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    descriptor: &wgpu::TextureDescriptor,
+    texture: &wgpu::Texture,
+) -> wgpu::Buffer {
     let texture_extent = descriptor.size;
 
     let bytes_per_piexel = match descriptor.format {
-        wgpu::TextureFormat::Rgba8UnormSrgb => 4,
-        wgpu::TextureFormat::Rgba8Unorm => 1,
+        wgpu::TextureFormat::Rgba8UnormSrgb
+        | wgpu::TextureFormat::Rgba8Unorm
+        | wgpu::TextureFormat::Bgra8UnormSrgb
+        | wgpu::TextureFormat::Bgra8Unorm
+        | wgpu::TextureFormat::Rgb10a2Unorm => 4,
         _ => panic!("unsupported pixel format"),
     };
 
@@ -122,7 +1109,8 @@ fn save(
         mapped_at_creation: false,
     });
 
-    assert!((texture_extent.width * bytes_per_piexel) % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT == 0);
+    assert!((texture_extent.width * bytes_per_piexel)
+        .is_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT));
 
     let command_encoder = {
         let mut encoder =
@@ -148,7 +1136,7 @@ fn save(
 
     device.poll(wgpu::Maintain::Wait);
 
-    block_on(create_png("demo.png", device, output_buffer, descriptor))
+    output_buffer
 }
 
 pub async fn create_png(
@@ -186,7 +1174,9 @@ pub async fn create_png(
         );
         png_encoder.set_depth(png::BitDepth::Eight);
         png_encoder.set_color(match texture_descriptor.format {
-            wgpu::TextureFormat::Rgba8UnormSrgb => png::ColorType::Rgba,
+            wgpu::TextureFormat::Rgba8UnormSrgb | wgpu::TextureFormat::Rgba8Unorm => {
+                png::ColorType::Rgba
+            }
             wgpu::TextureFormat::R8Unorm => png::ColorType::Grayscale,
             _ => panic!("unsupported pixel format"),
         });
@@ -201,3 +1191,1072 @@ pub async fn create_png(
         output_buffer.unmap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        f32::consts::{FRAC_PI_2, PI, TAU},
+        fs,
+        path::Path,
+    };
+
+    use super::*;
+    use crate::{
+        color::{opaque_grey, Background, ColorSpace, ColorStop, Hsla},
+        geometry::{Corners, Edges, ScaledPixels},
+        path_builder::{FillRule, LineCap, LineJoin, PathBuilder, StrokeStyle},
+        scene::{
+            BackdropBlur, BorderStyle, MonochromeSprite, OpacityLayer, Quad, Underline,
+            UnderlineStyle,
+        },
+    };
+
+    fn fallback_renderer() -> Renderer {
+        let gpu_ctx = WgpuContext::builder()
+            .force_fallback_adapter(true)
+            .build()
+            .unwrap();
+        Renderer::new(Arc::new(gpu_ctx))
+    }
+
+    fn quad(origin: [f32; 2], size: [f32; 2], corner_radius: f32, border_width: f32) -> Quad {
+        Quad {
+            order: 0,
+            border_style: BorderStyle::Solid,
+            bounds: Bounds {
+                origin: Point {
+                    x: ScaledPixels(origin[0]),
+                    y: ScaledPixels(origin[1]),
+                },
+                size: Size {
+                    width: ScaledPixels(size[0]),
+                    height: ScaledPixels(size[1]),
+                },
+            },
+            background: Hsla::green().into(),
+            border_colors: Edges::all(Hsla::red()),
+            corner_radii: Corners::all(Size::circular(ScaledPixels(corner_radius))),
+            border_widths: Edges {
+                top: ScaledPixels(border_width),
+                right: ScaledPixels(border_width),
+                bottom: ScaledPixels(border_width),
+                left: ScaledPixels(border_width),
+            },
+            border_dash_length: ScaledPixels(0.0),
+            border_dash_gap: ScaledPixels(0.0),
+            transformation: TransformationMatrix::unit(),
+        }
+    }
+
+    /// Compares the last frame against `goldens/{name}.png`, rendered by
+    /// llvmpipe. Set `WUI_UPDATE_GOLDENS` to rewrite it instead.
+    fn assert_golden(renderer: &Renderer, name: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/renderer/goldens")
+            .join(format!("{name}.png"));
+        if env::var_os("WUI_UPDATE_GOLDENS").is_some() {
+            renderer.save_png(path.to_str().unwrap());
+            return;
+        }
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut golden = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut golden).unwrap();
+
+        let pixels = renderer.read_pixels();
+        assert_eq!(pixels.len(), golden.len());
+        // Leaves room for rounding differences between software rasterizers.
+        let mismatch = pixels
+            .iter()
+            .zip(&golden)
+            .position(|(pixel, golden)| pixel.abs_diff(*golden) > 2);
+        if let Some(ix) = mismatch {
+            let (x, y) = (ix / 4 % 512, ix / 4 / 512);
+            panic!("{name} differs from {} at ({x}, {y})", path.display());
+        }
+    }
+
+    #[test]
+    fn test_msaa_golden() {
+        // Fractional bounds, so the edges of the square quad, which has no SDF,
+        // cut through pixels.
+        let scene = Scene {
+            quads: vec![
+                quad([20.5, 20.25], [100.3, 60.6], 0.0, 0.0),
+                quad([140.25, 20.5], [100.0, 100.0], 30.0, 4.0),
+            ],
+            ..Default::default()
+        };
+
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        assert_golden(&renderer, "analytic");
+        let analytic = renderer.read_pixels();
+
+        renderer.set_antialiasing(Antialiasing::Msaa4x);
+        assert_eq!(renderer.draw(&scene).len(), 1);
+        assert_golden(&renderer, "msaa4x");
+        assert_ne!(renderer.read_pixels(), analytic);
+    }
+
+    #[test]
+    fn test_gradient_golden() {
+        let stops = |start: Hsla, end: Hsla| {
+            [
+                ColorStop {
+                    color: start,
+                    percentage: 0.0,
+                },
+                ColorStop {
+                    color: end,
+                    percentage: 1.0,
+                },
+            ]
+        };
+        let red_to_blue = stops(Hsla::red(), Hsla::blue());
+        let with_background = |mut quad: Quad, background| {
+            quad.background = background;
+            quad
+        };
+        let scene = Scene {
+            quads: vec![
+                with_background(
+                    quad([10.0, 10.0], [300.0, 40.0], 0.0, 0.0),
+                    Background::linear_gradient(90.0, &red_to_blue),
+                ),
+                with_background(
+                    quad([10.0, 60.0], [300.0, 40.0], 0.0, 0.0),
+                    Background::linear_gradient(90.0, &red_to_blue)
+                        .with_color_space(ColorSpace::LinearSrgb),
+                ),
+                with_background(
+                    quad([10.0, 110.0], [300.0, 40.0], 0.0, 0.0),
+                    Background::linear_gradient(90.0, &red_to_blue)
+                        .with_color_space(ColorSpace::Oklab),
+                ),
+                with_background(
+                    quad([10.0, 170.0], [200.0, 120.0], 20.0, 2.0),
+                    Background::linear_gradient(
+                        135.0,
+                        &[
+                            ColorStop {
+                                color: Hsla::white(),
+                                percentage: 0.2,
+                            },
+                            ColorStop {
+                                color: Hsla::green(),
+                                percentage: 0.5,
+                            },
+                            ColorStop {
+                                color: Hsla::blue().opacity(0.0),
+                                percentage: 0.8,
+                            },
+                        ],
+                    ),
+                ),
+                with_background(
+                    quad([230.0, 170.0], [160.0, 160.0], 80.0, 0.0),
+                    Background::radial_gradient(
+                        Point { x: 0.3, y: 0.3 },
+                        &stops(Hsla::white(), Hsla::red()),
+                    ),
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        assert_golden(&renderer, "gradients");
+    }
+
+    #[test]
+    fn test_gradient_with_many_stops() {
+        // Five hard-edged bands, each between two stops at the same position.
+        let colors = [
+            Hsla::red(),
+            Hsla::green(),
+            Hsla::blue(),
+            Hsla::white(),
+            Hsla::black(),
+        ];
+        let stops = colors
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &color)| {
+                [i, i + 1].map(|edge| ColorStop {
+                    color,
+                    percentage: edge as f32 / colors.len() as f32,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut quad = quad([0.0, 0.0], [500.0, 20.0], 0.0, 0.0);
+        quad.background = Background::linear_gradient(90.0, &stops);
+        let mut renderer = fallback_renderer();
+        renderer.draw(&Scene {
+            quads: vec![quad],
+            ..Default::default()
+        });
+
+        let pixels = renderer.read_pixels();
+        let bands = (0..colors.len())
+            .map(|band| &pixels[(10 * 512 + band * 100 + 50) * 4..][..4])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bands,
+            [
+                [255, 0, 0, 255],
+                [0, 127, 0, 255],
+                [0, 0, 255, 255],
+                [255, 255, 255, 255],
+                [0, 0, 0, 255],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_golden() {
+        let pt = |x: f32, y: f32| Point {
+            x: ScaledPixels(x),
+            y: ScaledPixels(y),
+        };
+
+        let mut star = PathBuilder::new();
+        for i in 0..5 {
+            let angle = (i * 2) as f32 * TAU / 5.0;
+            let to = pt(100.0 + 80.0 * angle.sin(), 100.0 - 80.0 * angle.cos());
+            if i == 0 {
+                star.move_to(to);
+            } else {
+                star.line_to(to);
+            }
+        }
+        star.close();
+
+        let mut curve = PathBuilder::new();
+        curve.move_to(pt(220.0, 180.0));
+        curve.cubic_bezier_to(pt(260.0, 20.0), pt(340.0, 340.0), pt(380.0, 180.0));
+
+        let mut progress = PathBuilder::new();
+        progress.arc(pt(300.0, 380.0), ScaledPixels(80.0), -FRAC_PI_2, 1.5 * PI);
+
+        let scene = Scene {
+            quads: vec![quad([40.0, 300.0], [120.0, 120.0], 20.0, 0.0)],
+            paths: vec![
+                star.fill(FillRule::EvenOdd, Hsla::blue()).unwrap(),
+                curve
+                    .stroke(
+                        &StrokeStyle {
+                            width: ScaledPixels(8.0),
+                            join: LineJoin::Round,
+                            cap: LineCap::Round,
+                            ..Default::default()
+                        },
+                        Hsla::red(),
+                    )
+                    .unwrap(),
+                progress
+                    .stroke(
+                        &StrokeStyle {
+                            width: ScaledPixels(12.0),
+                            dashes: vec![ScaledPixels(20.0), ScaledPixels(10.0)],
+                            ..Default::default()
+                        },
+                        Hsla::white().opacity(0.8),
+                    )
+                    .unwrap(),
+            ],
+            ..Default::default()
+        };
+        // Multisampled without asking, as paths have no analytic coverage.
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        assert_golden(&renderer, "paths");
+
+        // Back to a single sample once the paths are gone, which redraws everything.
+        let quads = Scene {
+            quads: scene.quads.clone(),
+            ..Default::default()
+        };
+        assert_eq!(renderer.draw(&quads), [renderer.viewport()]);
+    }
+
+    #[test]
+    fn test_border_colors_and_elliptical_corners_golden() {
+        let elliptical = |mut quad: Quad, rx: f32, ry: f32| {
+            quad.background = opaque_grey(0.2, 1.0).into();
+            quad.corner_radii = Corners::all(Size {
+                width: ScaledPixels(rx),
+                height: ScaledPixels(ry),
+            });
+            quad
+        };
+        let four_colors = Edges {
+            top: Hsla::red(),
+            right: Hsla::green(),
+            bottom: Hsla::blue(),
+            left: Hsla::white(),
+        };
+
+        let mut sides = elliptical(quad([20.0, 20.0], [200.0, 120.0], 0.0, 8.0), 0.0, 0.0);
+        sides.border_colors = four_colors;
+        let mut uneven = elliptical(quad([260.0, 20.0], [200.0, 120.0], 0.0, 0.0), 60.0, 30.0);
+        uneven.border_colors = four_colors;
+        uneven.border_widths = Edges {
+            top: ScaledPixels(4.0),
+            right: ScaledPixels(12.0),
+            bottom: ScaledPixels(20.0),
+            left: ScaledPixels(8.0),
+        };
+        let mut dashed = elliptical(quad([260.0, 180.0], [200.0, 120.0], 0.0, 4.0), 80.0, 30.0);
+        dashed.border_style = BorderStyle::Dashed;
+        dashed.border_dash_length = ScaledPixels(12.0);
+        dashed.border_dash_gap = ScaledPixels(6.0);
+        let mut mixed = elliptical(quad([20.0, 340.0], [200.0, 120.0], 0.0, 6.0), 0.0, 0.0);
+        mixed.border_colors.left = Hsla::blue();
+        mixed.corner_radii.top_left = Size {
+            width: ScaledPixels(80.0),
+            height: ScaledPixels(40.0),
+        };
+        mixed.corner_radii.bottom_right = Size::circular(ScaledPixels(20.0));
+        let scene = Scene {
+            quads: vec![
+                sides,
+                uneven,
+                // An ellipse.
+                elliptical(quad([20.0, 180.0], [200.0, 120.0], 0.0, 3.0), 100.0, 60.0),
+                dashed,
+                mixed,
+            ],
+            ..Default::default()
+        };
+
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        assert_golden(&renderer, "border_colors");
+    }
+
+    #[test]
+    fn test_border_style_golden() {
+        use BorderStyle::*;
+
+        let styled = |mut quad: Quad, border_style, dash_length: f32, dash_gap: f32| {
+            quad.background = opaque_grey(0.2, 1.0).into();
+            quad.border_style = border_style;
+            quad.border_dash_length = ScaledPixels(dash_length);
+            quad.border_dash_gap = ScaledPixels(dash_gap);
+            quad
+        };
+        let mut uneven = styled(
+            quad([260.0, 150.0], [200.0, 100.0], 0.0, 2.0),
+            Dashed,
+            12.0,
+            6.0,
+        );
+        uneven.border_widths.bottom = ScaledPixels(6.0);
+        let scene = Scene {
+            quads: vec![
+                styled(
+                    quad([20.0, 20.0], [200.0, 100.0], 0.0, 2.0),
+                    Dashed,
+                    10.0,
+                    5.0,
+                ),
+                styled(
+                    quad([260.0, 20.0], [200.0, 100.0], 24.0, 3.0),
+                    Dashed,
+                    12.0,
+                    6.0,
+                ),
+                styled(
+                    quad([20.0, 150.0], [200.0, 100.0], 30.0, 4.0),
+                    Dotted,
+                    0.0,
+                    4.0,
+                ),
+                uneven,
+                styled(
+                    quad([20.0, 280.0], [120.0, 120.0], 60.0, 6.0),
+                    Dotted,
+                    0.0,
+                    6.0,
+                ),
+                // Without spacing the border stays solid.
+                styled(
+                    quad([260.0, 280.0], [120.0, 120.0], 10.0, 2.0),
+                    Dashed,
+                    0.0,
+                    0.0,
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        assert_golden(&renderer, "border_styles");
+    }
+
+    #[test]
+    fn test_underline_golden() {
+        use UnderlineStyle::*;
+
+        let underline =
+            |origin: [f32; 2], size: [f32; 2], thickness: f32, style, color| Underline {
+                order: 1,
+                pad: 0,
+                bounds: Bounds {
+                    origin: Point {
+                        x: ScaledPixels(origin[0]),
+                        y: ScaledPixels(origin[1]),
+                    },
+                    size: Size {
+                        width: ScaledPixels(size[0]),
+                        height: ScaledPixels(size[1]),
+                    },
+                },
+                color,
+                thickness: ScaledPixels(thickness),
+                style,
+            };
+        let mut text = quad([10.0, 10.0], [200.0, 14.0], 0.0, 0.0);
+        text.background = opaque_grey(0.5, 1.0).into();
+        let scene = Scene {
+            quads: vec![text],
+            underlines: vec![
+                // A strikethrough across the text.
+                underline([10.0, 16.0], [200.0, 2.0], 2.0, Straight, Hsla::red()),
+                underline([10.0, 40.0], [200.0, 1.0], 1.0, Straight, Hsla::blue()),
+                underline([10.0, 60.5], [200.0, 1.5], 1.5, Straight, Hsla::white()),
+                underline([10.0, 80.0], [200.0, 4.0], 1.0, Wavy, Hsla::red()),
+                underline([10.0, 100.0], [200.0, 8.0], 2.0, Wavy, Hsla::green()),
+            ],
+            ..Default::default()
+        };
+
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        assert_golden(&renderer, "underlines");
+    }
+
+    #[test]
+    fn test_interleaved_quad_batches_keep_their_instances() {
+        // Quads, an underline and quads again: two quad batches in one pass,
+        // each of which must draw its own instances.
+        let mut first = quad([10.0, 10.0], [40.0, 40.0], 0.0, 0.0);
+        first.background = Hsla::blue().into();
+        let mut second = quad([100.0, 10.0], [40.0, 40.0], 0.0, 0.0);
+        second.order = 2;
+        second.background = Hsla::green().into();
+        let underline = Underline {
+            order: 1,
+            pad: 0,
+            bounds: first.bounds,
+            color: Hsla::white(),
+            thickness: ScaledPixels(1.0),
+            style: UnderlineStyle::Straight,
+        };
+        let scene = Scene {
+            quads: vec![first, second],
+            underlines: vec![underline],
+            ..Default::default()
+        };
+        assert_eq!(scene.batches().into_iter().count(), 3);
+
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        let pixels = renderer.read_pixels();
+        let pixel = |x: usize, y: usize| &pixels[(y * 512 + x) * 4..][..4];
+        assert_eq!(pixel(30, 40), [0, 0, 255, 255]);
+        assert_eq!(pixel(120, 40), [0, 127, 0, 255]);
+    }
+
+    #[test]
+    fn test_sprites_are_skipped() {
+        let sprite = MonochromeSprite {
+            order: 1,
+            bounds: Bounds {
+                origin: Point::default(),
+                size: Size {
+                    width: ScaledPixels(10.0),
+                    height: ScaledPixels(10.0),
+                },
+            },
+            color: Hsla::red(),
+            transformation: TransformationMatrix::unit(),
+        };
+        let mut renderer = fallback_renderer();
+        renderer.draw(&Scene {
+            quads: vec![quad([0.0, 0.0], [10.0, 10.0], 0.0, 0.0)],
+            monochrome_sprites: vec![sprite],
+            ..Default::default()
+        });
+        assert_eq!(renderer.read_pixels()[..4], [0, 127, 0, 255]);
+    }
+
+    #[test]
+    fn test_upload_stats_count_the_instances_drawn() {
+        let scene = Scene {
+            quads: (0..3)
+                .map(|i| quad([10.0 + 50.0 * i as f32, 10.0], [40.0, 40.0], 0.0, 0.0))
+                .collect(),
+            ..Default::default()
+        };
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        // One write for all quads; kinds with no instances aren't uploaded.
+        assert_eq!(
+            renderer.upload_stats(),
+            UploadStats {
+                bytes: 3 * size_of::<QuadInstance>() as u64,
+                writes: 1,
+            }
+        );
+
+        let mut scene = scene;
+        let stops = [ColorStop::default(); 5];
+        scene.quads[1].background = Background::linear_gradient(0.0, &stops);
+        scene.quads[0].order = 1;
+        scene.underlines.push(Underline {
+            order: 0,
+            pad: 0,
+            bounds: scene.quads[0].bounds,
+            color: Hsla::white(),
+            thickness: ScaledPixels(1.0),
+            style: UnderlineStyle::Straight,
+        });
+        renderer.draw(&scene);
+        assert_eq!(
+            renderer.upload_stats(),
+            UploadStats {
+                bytes: (3 * size_of::<QuadInstance>()
+                    + stops.len() * size_of::<ColorStop>()
+                    + size_of::<Underline>()) as u64,
+                writes: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_transformation_golden() {
+        let pt = |x: f32, y: f32| Point {
+            x: ScaledPixels(x),
+            y: ScaledPixels(y),
+        };
+        let around = |center: Point<ScaledPixels>, transformation: TransformationMatrix| {
+            TransformationMatrix::unit()
+                .translate(center)
+                .compose(transformation)
+                .translate(pt(-center.x.0, -center.y.0))
+        };
+
+        // A spinner's segments, each rotated a little further around the center.
+        let spinner = (0..8).map(|i| {
+            let mut segment = quad([96.0, 20.0], [8.0, 30.0], 4.0, 0.0);
+            segment.background = Hsla::white().opacity((i + 1) as f32 / 8.0).into();
+            segment.transformation = around(
+                pt(100.0, 100.0),
+                TransformationMatrix::unit().rotate(i as f32 * TAU / 8.0),
+            );
+            segment
+        });
+        let mut sharp = quad([200.0, 40.0], [100.0, 60.0], 0.0, 0.0);
+        sharp.transformation = around(pt(250.0, 70.0), TransformationMatrix::unit().rotate(0.3));
+        let mut hovered = quad([340.0, 40.0], [100.0, 60.0], 12.0, 4.0);
+        hovered.background = Background::linear_gradient(
+            90.0,
+            &[
+                ColorStop {
+                    color: Hsla::blue(),
+                    percentage: 0.0,
+                },
+                ColorStop {
+                    color: Hsla::green(),
+                    percentage: 1.0,
+                },
+            ],
+        );
+        hovered.transformation = around(
+            pt(390.0, 70.0),
+            TransformationMatrix::unit().scale(Size {
+                width: 1.2,
+                height: 1.2,
+            }),
+        );
+
+        let mut triangle = PathBuilder::new();
+        triangle.move_to(pt(100.0, 220.0));
+        triangle.line_to(pt(160.0, 320.0));
+        triangle.line_to(pt(40.0, 320.0));
+        triangle.close();
+        let mut triangle = triangle.fill(FillRule::NonZero, Hsla::red()).unwrap();
+        triangle.transformation = TransformationMatrix::unit()
+            .translate(pt(200.0, 0.0))
+            .compose(around(
+                pt(100.0, 280.0),
+                TransformationMatrix::unit().rotate(PI),
+            ));
+
+        let scene = Scene {
+            quads: spinner.chain([sharp, hovered]).collect(),
+            paths: vec![triangle],
+            ..Default::default()
+        };
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        assert_golden(&renderer, "transformations");
+    }
+
+    #[test]
+    fn test_transformed_quad_damages_its_visual_bounds() {
+        let mut square = quad([0.0, 0.0], [100.0, 100.0], 0.0, 0.0);
+        let mut renderer = fallback_renderer();
+        renderer.draw(&Scene {
+            quads: vec![square.clone()],
+            ..Default::default()
+        });
+
+        square.transformation = TransformationMatrix::unit().translate(Point {
+            x: ScaledPixels(300.0),
+            y: ScaledPixels(300.0),
+        });
+        let damage = renderer.draw(&Scene {
+            quads: vec![square],
+            ..Default::default()
+        });
+        let moved = Bounds {
+            origin: Point {
+                x: DevicePixels(300),
+                y: DevicePixels(300),
+            },
+            size: Size {
+                width: DevicePixels(100),
+                height: DevicePixels(100),
+            },
+        };
+        assert!(damage.iter().any(|rect| rect.contains(&moved)));
+    }
+
+    /// Red and blue stripes with two rounded backdrop blurs over them, the
+    /// first tinted by a translucent quad sharing its draw order.
+    fn backdrop_blur_scene() -> Scene {
+        let stripes = (0..16).map(|i| {
+            let mut stripe = quad([i as f32 * 20.0, 0.0], [10.0, 300.0], 0.0, 0.0);
+            stripe.background = if i % 2 == 0 {
+                Hsla::red()
+            } else {
+                Hsla::blue()
+            }
+            .into();
+            stripe
+        });
+        let backdrop_blur =
+            |origin: [f32; 2], size: [f32; 2], corner_radius, blur_radius| BackdropBlur {
+                order: 1,
+                blur_radius: ScaledPixels(blur_radius),
+                bounds: Bounds {
+                    origin: Point {
+                        x: ScaledPixels(origin[0]),
+                        y: ScaledPixels(origin[1]),
+                    },
+                    size: Size {
+                        width: ScaledPixels(size[0]),
+                        height: ScaledPixels(size[1]),
+                    },
+                },
+                corner_radii: Corners::all(Size::circular(ScaledPixels(corner_radius))),
+            };
+        let mut tint = quad([20.0, 20.0], [200.0, 100.0], 16.0, 0.0);
+        tint.order = 1;
+        tint.background = Hsla::white().opacity(0.3).into();
+
+        Scene {
+            quads: stripes.chain([tint]).collect(),
+            backdrop_blurs: vec![
+                backdrop_blur([20.0, 20.0], [200.0, 100.0], 16.0, 16.0),
+                backdrop_blur([40.0, 160.0], [240.0, 100.0], 50.0, 4.0),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_backdrop_blur_golden() {
+        let mut renderer = fallback_renderer();
+        renderer.draw(&backdrop_blur_scene());
+        assert_golden(&renderer, "backdrop_blur");
+        let analytic = renderer.read_pixels();
+
+        // Blurs copy from the resolved samples.
+        renderer.set_antialiasing(Antialiasing::Msaa4x);
+        renderer.draw(&backdrop_blur_scene());
+        let center = (210 * 512 + 160) * 4;
+        let msaa = renderer.read_pixels();
+        assert!(msaa[center..center + 4]
+            .iter()
+            .zip(&analytic[center..center + 4])
+            .all(|(msaa, analytic)| msaa.abs_diff(*analytic) <= 2));
+    }
+
+    #[test]
+    fn test_backdrop_blur_redraws_everything_it_samples() {
+        let mut scene = backdrop_blur_scene();
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+
+        // Right of the first blur, close enough to be smeared into it.
+        scene.quads[11].background = Hsla::green().into();
+        let damage = renderer.draw(&scene);
+        let blurred = scene.backdrop_blurs[0].bounds.round_out();
+        assert!(damage.iter().any(|rect| rect.contains(&blurred)));
+
+        let mut full_redraw = fallback_renderer();
+        full_redraw.draw(&scene);
+        assert_eq!(renderer.read_pixels(), full_redraw.read_pixels());
+    }
+
+    /// Overlapping red and blue squares faded together over white, with a
+    /// green square in a nested layer faded again.
+    fn opacity_layer_scene() -> Scene {
+        let mut background = quad([0.0, 0.0], [512.0, 512.0], 0.0, 0.0);
+        background.background = Hsla::white().into();
+        let square = |order, origin, color: Hsla| {
+            let mut square = quad(origin, [100.0, 100.0], 10.0, 0.0);
+            square.order = order;
+            square.background = color.into();
+            square
+        };
+        let layer = |orders, origin: [f32; 2], opacity| OpacityLayer {
+            orders,
+            bounds: Bounds {
+                origin: Point {
+                    x: ScaledPixels(origin[0]),
+                    y: ScaledPixels(origin[1]),
+                },
+                size: Size {
+                    width: ScaledPixels(200.0),
+                    height: ScaledPixels(200.0),
+                },
+            },
+            opacity,
+        };
+
+        Scene {
+            quads: vec![
+                background,
+                square(1, [40.0, 40.0], Hsla::red()),
+                square(2, [90.0, 90.0], Hsla::blue()),
+                square(3, [140.0, 140.0], Hsla::green()),
+            ],
+            opacity_layers: vec![
+                layer(1..4, [20.0, 20.0], 0.5),
+                layer(3..4, [120.0, 120.0], 0.5),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_opacity_layer_golden() {
+        let mut renderer = fallback_renderer();
+        renderer.draw(&opacity_layer_scene());
+        assert_golden(&renderer, "opacity_layer");
+
+        // The blue square covers the red one within the layer, so it looks the
+        // same where they overlap as where it's alone.
+        let pixels = renderer.read_pixels();
+        let pixel = |x: usize, y: usize| &pixels[(y * 512 + x) * 4..][..4];
+        assert_eq!(pixel(110, 110), pixel(170, 100));
+        assert_ne!(pixel(110, 110), pixel(60, 60));
+
+        // Straight alpha layers are premultiplied by blending, and undone.
+        renderer.set_alpha_mode(wgpu::CompositeAlphaMode::PostMultiplied);
+        renderer.draw(&opacity_layer_scene());
+        assert!(renderer
+            .read_pixels()
+            .iter()
+            .zip(&pixels)
+            .all(|(straight, premultiplied)| straight.abs_diff(*premultiplied) <= 1));
+
+        // Layers are composited from their resolved samples.
+        renderer.set_antialiasing(Antialiasing::Msaa4x);
+        renderer.draw(&opacity_layer_scene());
+        let msaa = renderer.read_pixels();
+        assert_eq!(msaa[(110 * 512 + 110) * 4..][..4], *pixel(110, 110));
+    }
+
+    #[test]
+    fn test_opacity_layer_textures_are_pooled() {
+        let mut scene = opacity_layer_scene();
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        assert_eq!(renderer.texture_pool.len(), 2);
+
+        scene.quads[3].background = Hsla::red().into();
+        assert!(!renderer.draw(&scene).is_empty());
+        assert_eq!(renderer.texture_pool.len(), 2);
+
+        // Dropped after a frame without layers.
+        scene.opacity_layers.clear();
+        renderer.draw(&scene);
+        assert_eq!(renderer.texture_pool.len(), 0);
+    }
+
+    fn device_bounds(left: i32, top: i32, right: i32, bottom: i32) -> Bounds<DevicePixels> {
+        Bounds::from_corners(
+            Point {
+                x: DevicePixels(left),
+                y: DevicePixels(top),
+            },
+            Point {
+                x: DevicePixels(right),
+                y: DevicePixels(bottom),
+            },
+        )
+    }
+
+    #[test]
+    fn test_merge_damage_unions_overlapping_rects() {
+        let mut merged = merge_damage(vec![
+            device_bounds(0, 0, 10, 10),
+            device_bounds(5, 5, 15, 15),
+            device_bounds(100, 100, 110, 110),
+        ]);
+        merged.sort_by_key(|rect| rect.origin.x);
+        assert_eq!(
+            merged,
+            [
+                device_bounds(0, 0, 15, 15),
+                device_bounds(100, 100, 110, 110)
+            ]
+        );
+
+        // The union of overlapping rects can reach one that overlapped neither.
+        let merged = merge_damage(vec![
+            device_bounds(0, 0, 10, 10),
+            device_bounds(20, 20, 30, 30),
+            device_bounds(5, 5, 25, 25),
+            device_bounds(0, 25, 4, 30),
+        ]);
+        assert_eq!(merged, [device_bounds(0, 0, 30, 30)]);
+    }
+
+    #[test]
+    fn test_merge_damage_collapses_too_many_rects() {
+        let rects = (0..MAX_DAMAGE_RECTS as i32 + 1)
+            .map(|i| device_bounds(i * 20, 0, i * 20 + 10, 10))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            merge_damage(rects[..MAX_DAMAGE_RECTS].to_vec()).len(),
+            MAX_DAMAGE_RECTS
+        );
+        assert_eq!(
+            merge_damage(rects),
+            [device_bounds(0, 0, MAX_DAMAGE_RECTS as i32 * 20 + 10, 10)]
+        );
+    }
+
+    #[test]
+    fn test_choose_alpha_mode() {
+        use wgpu::CompositeAlphaMode::*;
+
+        assert_eq!(choose_alpha_mode(&[Opaque, PreMultiplied], false), Opaque);
+        assert_eq!(
+            choose_alpha_mode(&[Opaque, PreMultiplied], true),
+            PreMultiplied
+        );
+        assert_eq!(
+            choose_alpha_mode(&[Opaque, PostMultiplied], true),
+            PostMultiplied
+        );
+        assert_eq!(choose_alpha_mode(&[Inherit], false), Inherit);
+    }
+
+    #[test]
+    fn test_translucent_quad_over_transparent_clear_is_premultiplied() {
+        let mut renderer = fallback_renderer();
+        renderer.set_alpha_mode(wgpu::CompositeAlphaMode::PreMultiplied);
+        renderer.set_clear_color(Hsla::transparent_black());
+        let mut quad = quad([0.0, 0.0], [10.0, 10.0], 0.0, 0.0);
+        quad.background = Hsla::green().opacity(0.5).into();
+        renderer.draw(&Scene {
+            quads: vec![quad],
+            ..Default::default()
+        });
+
+        let pixels = renderer.read_pixels();
+        // Green is #008000, 0.214 in linear space; halved and sRGB encoded.
+        assert_eq!(pixels[..4], [0, 92, 0, 128]);
+        assert_eq!(pixels[4 * 20..][..4], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_unorm_target_is_encoded_like_srgb_target() {
+        let scene = Scene {
+            quads: vec![quad([10.0, 10.0], [100.0, 50.0], 0.0, 0.0)],
+            ..Default::default()
+        };
+        let mut renderer = fallback_renderer();
+        // Opaque: translucent pixels are premultiplied in linear space when the
+        // format encodes them, and legitimately differ.
+        renderer.set_clear_color(opaque_grey(0.5, 1.0));
+        renderer.draw(&scene);
+        let srgb = renderer.read_pixels();
+
+        renderer.set_format(wgpu::TextureFormat::Rgba8Unorm);
+        renderer.draw(&scene);
+        let unorm = renderer.read_pixels();
+
+        assert_eq!(srgb[(20 * 512 + 20) * 4..][..4], [0, 127, 0, 255]);
+        assert!(srgb
+            .iter()
+            .zip(&unorm)
+            .all(|(srgb, unorm)| srgb.abs_diff(*unorm) <= 1));
+    }
+
+    #[test]
+    fn test_10_bit_unorm_target_is_encoded_like_srgb_target() {
+        let scene = Scene {
+            quads: vec![quad([10.0, 10.0], [100.0, 50.0], 0.0, 0.0)],
+            ..Default::default()
+        };
+        let mut renderer = fallback_renderer();
+        renderer.set_clear_color(opaque_grey(0.5, 1.0));
+        renderer.draw(&scene);
+        let srgb = renderer.read_pixels();
+
+        renderer.set_format(wgpu::TextureFormat::Rgb10a2Unorm);
+        renderer.draw(&scene);
+        let unorm = renderer.read_pixels();
+
+        for (srgb, unorm) in srgb.chunks(4).zip(unorm.chunks(4)) {
+            let unorm = u32::from_le_bytes(unorm.try_into().unwrap());
+            let channels = [0, 10, 20].map(|shift| (unorm >> shift & 0x3ff) as f32 / 1023.0);
+            for (srgb, unorm) in srgb.iter().zip(channels) {
+                assert!((*srgb as f32 / 255.0 - unorm).abs() <= 1.5 / 255.0);
+            }
+            assert_eq!(unorm >> 30, 3);
+        }
+    }
+
+    #[test]
+    fn test_capture_in_another_format() {
+        let scene = Scene {
+            quads: vec![quad([10.0, 10.0], [100.0, 50.0], 0.0, 0.0)],
+            ..Default::default()
+        };
+        let mut renderer = fallback_renderer();
+        renderer.draw(&scene);
+        let capture = renderer.capture(&scene, wgpu::TextureFormat::Bgra8Unorm);
+
+        let pixels = renderer.read_pixels();
+        assert!(pixels.chunks(4).zip(capture.chunks(4)).all(|(rgba, bgra)| {
+            [bgra[2], bgra[1], bgra[0], bgra[3]]
+                .iter()
+                .zip(rgba)
+                .all(|(bgra, rgba)| bgra.abs_diff(*rgba) <= 1)
+        }));
+        // Capturing doesn't count as drawing the surface.
+        assert!(renderer.draw(&scene).is_empty());
+    }
+
+    #[test]
+    fn test_draw_recovers_from_device_loss() {
+        let mut renderer = fallback_renderer();
+        let scene = Scene::default();
+        assert_eq!(renderer.draw(&scene).len(), 1);
+
+        let lost_ctx = renderer.gpu_ctx().clone();
+        lost_ctx.device.destroy();
+        assert!(lost_ctx.is_lost());
+
+        // Unchanged, so only drawn again because everything was recreated.
+        assert_eq!(renderer.draw(&scene).len(), 1);
+        assert!(!Arc::ptr_eq(renderer.gpu_ctx(), &lost_ctx));
+        assert!(!renderer.gpu_ctx().is_lost());
+        assert!(renderer.recovery_error().is_none());
+    }
+
+    #[test]
+    fn test_device_loss_keeps_shader_defines() {
+        let scene = Scene {
+            quads: vec![quad([10.0, 10.0], [100.0, 50.0], 25.0, 0.0)],
+            ..Default::default()
+        };
+        let mut renderer = fallback_renderer();
+        renderer
+            .set_shader_defines(&[shaders::DEBUG_QUAD_BOUNDS])
+            .unwrap();
+        renderer.draw(&scene);
+        let debug = renderer.read_pixels();
+
+        renderer.gpu_ctx().device.destroy();
+        renderer.draw(&scene);
+        assert_eq!(renderer.read_pixels(), debug);
+    }
+
+    /// A copy of the shader directory to edit, unique to the test `name`.
+    fn copy_shaders(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("wui-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let shader_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/renderer/shaders");
+        for entry in fs::read_dir(shader_dir).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_broken_shader_keeps_previous_pipelines() {
+        let dir = copy_shaders("broken-shader");
+        let mut renderer = fallback_renderer();
+        renderer.watch_shaders(&dir);
+        renderer.draw(&Scene::default());
+        assert!(renderer.shader_error().is_none());
+
+        let quad_path = dir.join("quad.wgsl");
+        let mut source = fs::read_to_string(&quad_path).unwrap();
+        let line = source.lines().count() as u32 + 3;
+        source.push_str("\nfn broken() -> f32 {\n  return missing;\n}\n");
+        fs::write(&quad_path, source).unwrap();
+
+        // Drawn with the pipelines built before the edit.
+        renderer.draw(&Scene {
+            quads: vec![quad([10.0, 10.0], [100.0, 50.0], 0.0, 0.0)],
+            ..Default::default()
+        });
+        let error = renderer.shader_error().unwrap();
+        assert_eq!(error.path, quad_path);
+        assert_eq!(error.location, Some((line, 10)));
+        assert_eq!(
+            renderer.read_pixels()[(20 * 512 + 20) * 4..][..4],
+            [0, 127, 0, 255]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pipeline_error_names_its_entry_file() {
+        let dir = copy_shaders("pipeline-error");
+        let mut renderer = fallback_renderer();
+        renderer.watch_shaders(&dir);
+        renderer.draw(&Scene::default());
+
+        // Valid WGSL for naga, but the device lacks push constants.
+        let underline_path = dir.join("underline.wgsl");
+        let mut source = fs::read_to_string(&underline_path).unwrap();
+        source.push_str("\nvar<push_constant> unsupported: f32;\n");
+        fs::write(&underline_path, source).unwrap();
+
+        renderer.draw(&Scene::default());
+        let error = renderer.shader_error().unwrap();
+        assert_eq!(error.path, underline_path);
+        assert_eq!(error.location, None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
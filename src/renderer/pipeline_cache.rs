@@ -0,0 +1,195 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{context::WgpuContext, shaders::Shaders};
+
+/// The driver's compiled pipelines, persisted under `$XDG_CACHE_HOME/wui/` so a
+/// shell started at login doesn't pay for shader compilation on every boot.
+///
+/// Files are named after the adapter (see [`wgpu::util::pipeline_cache_key`]),
+/// the shader [variant](Shaders::variant) and a hash of the shader sources.
+/// Editing a shader leaves the old file for the same adapter and variant
+/// stale, so it's deleted on load; switching adapters or toggling defines in
+/// dev mode simply picks a different file.
+///
+/// Pipelines are added to the cache as they're built, and it's written back
+/// once, when dropped.
+pub(crate) struct PipelineCache {
+    cache: wgpu::PipelineCache,
+    path: PathBuf,
+    modified: bool,
+}
+
+impl PipelineCache {
+    /// Returns `None` when the backend doesn't support pipeline caches, which
+    /// currently means anything but Vulkan.
+    pub fn load(gpu_ctx: &WgpuContext, shaders: &Shaders) -> Option<Self> {
+        if !gpu_ctx
+            .device
+            .features()
+            .contains(wgpu::Features::PIPELINE_CACHE)
+        {
+            return None;
+        }
+        let adapter_key = wgpu::util::pipeline_cache_key(&gpu_ctx.adapter_info)?;
+        let dir = cache_dir(env::var_os("XDG_CACHE_HOME"), env::var_os("HOME"))?.join("wui");
+        let prefix = file_prefix(&adapter_key, shaders.variant);
+        let path = dir.join(format!("{prefix}{:016x}", shaders.hash()));
+
+        remove_stale(&dir, &prefix, &path);
+        let data = fs::read(&path).ok();
+
+        // SAFETY: the data was produced by `get_data` for an adapter with the
+        // same cache key, and `fallback` makes wgpu discard it if the driver
+        // rejects it anyway.
+        let cache = unsafe {
+            gpu_ctx
+                .device
+                .create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                    label: Some("pipeline cache"),
+                    data: data.as_deref(),
+                    fallback: true,
+                })
+        };
+
+        Some(Self {
+            cache,
+            path,
+            modified: false,
+        })
+    }
+
+    pub fn cache(&self) -> &wgpu::PipelineCache {
+        &self.cache
+    }
+
+    /// Notes that pipelines were built through the cache, so it's saved.
+    pub fn mark_modified(&mut self) {
+        self.modified = true;
+    }
+
+    /// Writes the cache back to disk, through a temporary file so a crash
+    /// mid-write can't leave a truncated cache behind.
+    fn save(&self) -> io::Result<()> {
+        let Some(data) = self.cache.get_data() else {
+            return Ok(());
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, data)?;
+        fs::rename(temp_path, &self.path)
+    }
+}
+
+impl Drop for PipelineCache {
+    fn drop(&mut self) {
+        // Nothing can be done about a failed save this late; the pipelines are
+        // just compiled again on the next start.
+        if self.modified {
+            self.save().ok();
+        }
+    }
+}
+
+/// The base directory for caches, from the values of `$XDG_CACHE_HOME` and
+/// `$HOME`.
+fn cache_dir(xdg_cache_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    xdg_cache_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.map(|home| Path::new(&home).join(".cache")))
+}
+
+/// The start of the names of every cache for this adapter and shader variant,
+/// which end with the hash of the sources.
+fn file_prefix(adapter_key: &str, variant: u64) -> String {
+    format!("{adapter_key}_{variant:016x}_")
+}
+
+/// Deletes the caches starting with `prefix` that were built from other
+/// sources than `current`.
+fn remove_stale(dir: &Path, prefix: &str, current: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_stale = path != current
+            && entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with(prefix));
+        if is_stale {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_dir() {
+        let cache_dir = |xdg_cache_home: Option<&str>, home: Option<&str>| {
+            cache_dir(xdg_cache_home.map(OsString::from), home.map(OsString::from))
+        };
+        assert_eq!(
+            cache_dir(Some("/xdg/cache"), Some("/home/me")),
+            Some(PathBuf::from("/xdg/cache"))
+        );
+        // The spec says to ignore relative paths.
+        assert_eq!(
+            cache_dir(Some("cache"), Some("/home/me")),
+            Some(PathBuf::from("/home/me/.cache"))
+        );
+        assert_eq!(
+            cache_dir(None, Some("/home/me")),
+            Some(PathBuf::from("/home/me/.cache"))
+        );
+        assert_eq!(cache_dir(None, None), None);
+    }
+
+    #[test]
+    fn test_remove_stale() {
+        let dir = env::temp_dir().join(format!("wui-pipeline-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let release = file_prefix("adapter", 1);
+        assert_eq!(release, "adapter_0000000000000001_");
+        let dev = file_prefix("adapter", 2);
+        let other_adapter = file_prefix("other", 1);
+        for name in [
+            format!("{release}{:016x}", 10),
+            format!("{release}{:016x}", 11),
+            format!("{dev}{:016x}", 10),
+            format!("{other_adapter}{:016x}", 10),
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        // Only the other sources for the same adapter and variant are stale.
+        remove_stale(&dir, &release, &dir.join(format!("{release}{:016x}", 11)));
+        let mut names = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                format!("{release}{:016x}", 11),
+                format!("{dev}{:016x}", 10),
+                format!("{other_adapter}{:016x}", 10),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
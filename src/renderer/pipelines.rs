@@ -1,138 +1,646 @@
-use crate::scene::Quad;
+use std::{collections::HashMap, ops::Range};
+
+use futures::executor::block_on;
+
+use crate::{
+    color::ColorStop,
+    scene::{BackdropBlur, Underline},
+};
 
 use super::{
     context::WgpuContext,
     gpu_vec::{GPUVec, INIT_CAPACITY},
-    GlobalParams,
+    pipeline_cache::PipelineCache,
+    shaders::Shaders,
+    upload_belt::UploadBelt,
+    GlobalParams, LayerComposite, QuadInstance, TransformedPathVertex,
 };
 
+/// What a render target needs pipelines built for; each one gets its own
+/// [`PipelineVariant`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct PipelineConfig {
+    pub format: wgpu::TextureFormat,
+    /// Must match the render target: 1 unless it's multisampled.
+    pub sample_count: u32,
+    /// Whether shaders output premultiplied colors, which also selects the
+    /// matching blend state.
+    pub premultiplied_alpha: bool,
+}
+
+impl PipelineConfig {
+    /// Shaders output linear colors, which `*Srgb` formats encode on write.
+    /// Unorm formats would store them as is, including 10 and 16-bit ones such
+    /// as `Rgb10a2Unorm` that have no sRGB variant, so shaders encode them
+    /// first; blending then happens on encoded values, as most toolkits do.
+    /// Float formats are linear and need neither.
+    pub fn encode_srgb(&self) -> bool {
+        use wgpu::TextureFormat::*;
+        let float = matches!(
+            self.format,
+            R16Float
+                | Rg16Float
+                | Rgba16Float
+                | R32Float
+                | Rg32Float
+                | Rgba32Float
+                | Rg11b10Ufloat
+                | Rgb9e5Ufloat
+        );
+        !self.format.is_srgb() && !float
+    }
+
+    /// Values for the `override` declarations in `common.wgsl`.
+    fn constants(&self) -> HashMap<String, f64> {
+        HashMap::from([("encode_srgb".to_string(), self.encode_srgb() as u8 as f64)])
+    }
+
+    fn blend_state(&self) -> wgpu::BlendState {
+        if self.premultiplied_alpha {
+            wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING
+        } else {
+            wgpu::BlendState::ALPHA_BLENDING
+        }
+    }
+}
+
+/// Uniforms shared by every pipeline, bound once per render pass at group 0.
+pub(crate) struct Globals {
+    pub bind_group: wgpu::BindGroup,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub params_buffer: GPUVec<GlobalParams>,
+}
+
+impl Globals {
+    fn new(device: &wgpu::Device) -> Self {
+        let params_buffer = GPUVec::<GlobalParams>::new_uniforms(device, "global_params");
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("globals bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("globals bind group"),
+            layout: &bind_group_layout,
+            entries: &[params_buffer.bind_group_entry(0)],
+        });
+
+        Self {
+            bind_group,
+            bind_group_layout,
+            params_buffer,
+        }
+    }
+
+    /// Uploads the params for this frame. Called once per frame, not per batch.
+    pub(crate) fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        global_params: GlobalParams,
+    ) {
+        self.params_buffer.update(device, queue, &[global_params]);
+    }
+}
+
+/// The stops of every gradient drawn in a frame, bound at group 2 by the quad
+/// pipeline. Quads refer to theirs by offset and count.
+pub(crate) struct GradientStops {
+    pub bind_group: wgpu::BindGroup,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    data_buffer: GPUVec<ColorStop>,
+}
+
+impl GradientStops {
+    fn new(device: &wgpu::Device) -> Self {
+        let data_buffer = GPUVec::<ColorStop>::new(device, INIT_CAPACITY, "gradient_stops");
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gradient stops bind group layout"),
+            entries: &[GPUVec::<ColorStop>::bind_group_layout_entry(0)],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gradient stops bind group"),
+            layout: &bind_group_layout,
+            entries: &[data_buffer.bind_group_entry(0)],
+        });
+
+        Self {
+            bind_group,
+            bind_group_layout,
+            data_buffer,
+        }
+    }
+
+    /// Starts collecting stops for a new frame.
+    pub(crate) fn clear(&mut self) {
+        self.data_buffer.clear();
+    }
+
+    /// Queues the stops of a gradient, returning their range.
+    pub(crate) fn push(&mut self, stops: &[ColorStop]) -> Range<u32> {
+        self.data_buffer.push(stops)
+    }
+
+    /// Uploads every gradient queued since [`GradientStops::clear`].
+    pub(crate) fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        belt: &mut UploadBelt,
+    ) {
+        if self.data_buffer.upload(device, encoder, belt) {
+            self.bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("gradient stops bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[self.data_buffer.bind_group_entry(0)],
+            });
+        }
+    }
+}
+
+/// A primitive kind's shader and instance data, bound at group 1. Its render
+/// pipelines are built per [`PipelineConfig`] by [`Pipelines::prepare`].
+///
+/// Kinds that also sample textures pass their layouts as
+/// `extra_bind_group_layouts`, bound from group 2 on.
 pub(crate) struct PipelineCtx<T: Copy> {
-    pub pipeline: wgpu::RenderPipeline,
     pub bind_group: wgpu::BindGroup,
     pub bind_group_layout: wgpu::BindGroupLayout,
-    pub global_params_buffer: GPUVec<GlobalParams>,
     pub data_buffer: GPUVec<T>,
+    shader_source: String,
+    pipeline_layout: wgpu::PipelineLayout,
+    topology: wgpu::PrimitiveTopology,
+    label: &'static str,
 }
 
 impl<T: Copy> PipelineCtx<T> {
     fn new(
         device: &wgpu::Device,
-        shader: &wgpu::ShaderModule,
-        color_targets: &[Option<wgpu::ColorTargetState>],
-        label: &str,
+        globals: &Globals,
+        shader_source: &str,
+        topology: wgpu::PrimitiveTopology,
+        extra_bind_group_layouts: &[&wgpu::BindGroupLayout],
+        label: &'static str,
     ) -> Self {
-        let global_params_buffer = GPUVec::<GlobalParams>::new_uniforms(device, "global_params");
         let data_buffer = GPUVec::<T>::new(device, INIT_CAPACITY, "data");
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some(format!("{label}s bind group layout").as_str()),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                GPUVec::<T>::bind_group_layout_entry(1),
-            ],
+            entries: &[GPUVec::<T>::bind_group_layout_entry(0)],
         });
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some(format!("{label}s bind group").as_str()),
             layout: &bind_group_layout,
-            entries: &[
-                global_params_buffer.bind_group_entry(0),
-                data_buffer.bind_group_entry(1),
-            ],
+            entries: &[data_buffer.bind_group_entry(0)],
         });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some(format!("{label}s pipeline layout").as_str()),
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[
+                &[&globals.bind_group_layout, &bind_group_layout],
+                extra_bind_group_layouts,
+            ]
+            .concat(),
             push_constant_ranges: &[],
         });
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+
+        Self {
+            bind_group,
+            bind_group_layout,
+            data_buffer,
+            shader_source: shader_source.to_string(),
+            pipeline_layout,
+            topology,
+            label,
+        }
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        config: &PipelineConfig,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> wgpu::RenderPipeline {
+        let label = self.label;
+        // Each variant gets its own module, as the GL backend caches programs
+        // per module and entry point, ignoring the override constants.
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(format!("{label} shader").as_str()),
+            source: wgpu::ShaderSource::Wgsl(self.shader_source.as_str().into()),
+        });
+        let constants = config.constants();
+        let compilation_options = wgpu::PipelineCompilationOptions {
+            constants: &constants,
+            ..Default::default()
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some(format!("{label}s pipeline").as_str()),
-            layout: Some(&pipeline_layout),
+            layout: Some(&self.pipeline_layout),
             vertex: wgpu::VertexState {
-                module: shader,
+                module: &shader,
                 entry_point: Some(format!("vs_{label}").as_str()),
                 buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                compilation_options: compilation_options.clone(),
             },
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                topology: self.topology,
                 ..Default::default()
             },
             fragment: Some(wgpu::FragmentState {
-                module: shader,
+                module: &shader,
                 entry_point: Some(format!("fs_{label}").as_str()),
-                targets: color_targets,
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    write_mask: wgpu::ColorWrites::default(),
+                    format: config.format,
+                    blend: Some(config.blend_state()),
+                })],
+                compilation_options,
             }),
             depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
+            multisample: wgpu::MultisampleState {
+                count: config.sample_count,
+                ..Default::default()
+            },
             multiview: None,
-            cache: None,
-        });
+            cache,
+        })
+    }
 
-        Self {
-            pipeline,
-            bind_group,
-            bind_group_layout,
-            global_params_buffer,
-            data_buffer,
-        }
+    /// Starts collecting instances for a new frame.
+    pub(crate) fn clear(&mut self) {
+        self.data_buffer.clear();
     }
 
-    pub(crate) fn update(
+    /// Queues a batch of instances, returning the range to draw it with.
+    pub(crate) fn push(&mut self, data: &[T]) -> Range<u32> {
+        self.data_buffer.push(data)
+    }
+
+    /// Queues a batch of instances built on the fly, returning its range.
+    pub(crate) fn extend(&mut self, data: impl IntoIterator<Item = T>) -> Range<u32> {
+        self.data_buffer.extend(data)
+    }
+
+    /// Uploads every batch queued since [`PipelineCtx::clear`].
+    pub(crate) fn upload(
         &mut self,
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        global_params: GlobalParams,
-        data: &[T],
+        encoder: &mut wgpu::CommandEncoder,
+        belt: &mut UploadBelt,
     ) {
-        self.global_params_buffer
-            .update(device, queue, &[global_params]);
-
-        if self.data_buffer.update(device, queue, data) {
+        if self.data_buffer.upload(device, encoder, belt) {
             self.bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some("data bind group"),
                 layout: &self.bind_group_layout,
-                entries: &[
-                    self.global_params_buffer.bind_group_entry(0),
-                    self.data_buffer.bind_group_entry(1),
-                ],
+                entries: &[self.data_buffer.bind_group_entry(0)],
             });
         }
     }
 }
 
+/// A pipeline wgpu rejected although naga accepted its shader, e.g. for using
+/// a feature the device lacks.
+pub(crate) struct PipelineError {
+    /// The shader file the pipeline was built from.
+    pub entry: String,
+    pub source: wgpu::Error,
+}
+
+/// The render pipelines for one [`PipelineConfig`].
+pub(crate) struct PipelineVariant {
+    pub quads: wgpu::RenderPipeline,
+    pub paths: wgpu::RenderPipeline,
+    pub underlines: wgpu::RenderPipeline,
+    pub backdrop_blurs: wgpu::RenderPipeline,
+    pub opacity_layers: wgpu::RenderPipeline,
+    /// Render a level of a [`BlurChain`](super::blur::BlurChain) from the one
+    /// above or below it. Always single sampled, in the target's format.
+    pub blur_down: wgpu::RenderPipeline,
+    pub blur_up: wgpu::RenderPipeline,
+    /// Overwrites the scissor rect with the render pass blend constant.
+    pub clear: wgpu::RenderPipeline,
+}
+
+/// Everything needed to draw, shared by all render targets. Only the render
+/// pipelines depend on the target, and are built the first time a target
+/// with a new format or sample count is drawn to, e.g. a `Bgra8Unorm` surface
+/// and an `Rgba8UnormSrgb` capture.
 pub(crate) struct Pipelines {
-    pub quads: PipelineCtx<Quad>,
+    pub globals: Globals,
+    /// Also binds the gradient stops at group 2.
+    pub quads: PipelineCtx<QuadInstance>,
+    pub gradient_stops: GradientStops,
+    /// Indexed by vertex rather than instance: each path is a triangle list.
+    pub paths: PipelineCtx<TransformedPathVertex>,
+    pub underlines: PipelineCtx<Underline>,
+    /// Also binds the blurred backdrop at group 2.
+    pub backdrop_blurs: PipelineCtx<BackdropBlur>,
+    /// Also binds the layer's texture at group 2.
+    pub opacity_layers: PipelineCtx<LayerComposite>,
+    /// A texture with its sampler: a level of a blur chain, bound at group 1
+    /// by the blur passes and at group 2 by backdrop blurs, or an opacity
+    /// layer, bound at group 2 when it's composited.
+    pub texture_layout: wgpu::BindGroupLayout,
+    pub linear_sampler: wgpu::Sampler,
+    blur_source: String,
+    blur_pipeline_layout: wgpu::PipelineLayout,
+    clear_source: String,
+    clear_pipeline_layout: wgpu::PipelineLayout,
+    variants: HashMap<PipelineConfig, PipelineVariant>,
+    cache: Option<PipelineCache>,
 }
 
 impl Pipelines {
-    pub fn new(gpu_ctx: &WgpuContext) -> Self {
-        let shader = gpu_ctx
-            .device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: None,
-                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
-                    "shader.wgsl"
-                ))),
+    pub fn new(gpu_ctx: &WgpuContext, shaders: &Shaders) -> Self {
+        let device = &gpu_ctx.device;
+        let globals = Globals::new(device);
+        let gradient_stops = GradientStops::new(device);
+        let clear_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("clear pipeline layout"),
+                // Unused by the shader, but keeps the layout compatible with the other
+                // pipelines so the globals stay bound when switching between them.
+                bind_group_layouts: &[&globals.bind_group_layout],
+                push_constant_ranges: &[],
             });
 
-        let color_targets = &[Some(wgpu::ColorTargetState {
-            write_mask: wgpu::ColorWrites::default(),
-            // TODO: texture format should get from a surface
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-        })];
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        // The blur filters rely on bilinear taps between texels.
+        let linear_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("linear sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let blur_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("blur pipeline layout"),
+            // The globals are unused too, as for the clear pipeline.
+            bind_group_layouts: &[&globals.bind_group_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
 
         Self {
-            quads: PipelineCtx::new(&gpu_ctx.device, &shader, color_targets, "quad"),
+            quads: PipelineCtx::new(
+                device,
+                &globals,
+                &shaders.quad,
+                wgpu::PrimitiveTopology::TriangleStrip,
+                &[&gradient_stops.bind_group_layout],
+                "quad",
+            ),
+            gradient_stops,
+            paths: PipelineCtx::new(
+                device,
+                &globals,
+                &shaders.path,
+                wgpu::PrimitiveTopology::TriangleList,
+                &[],
+                "path",
+            ),
+            underlines: PipelineCtx::new(
+                device,
+                &globals,
+                &shaders.underline,
+                wgpu::PrimitiveTopology::TriangleStrip,
+                &[],
+                "underline",
+            ),
+            backdrop_blurs: PipelineCtx::new(
+                device,
+                &globals,
+                &shaders.backdrop_blur,
+                wgpu::PrimitiveTopology::TriangleStrip,
+                &[&texture_layout],
+                "backdrop_blur",
+            ),
+            opacity_layers: PipelineCtx::new(
+                device,
+                &globals,
+                &shaders.opacity_layer,
+                wgpu::PrimitiveTopology::TriangleStrip,
+                &[&texture_layout],
+                "opacity_layer",
+            ),
+            texture_layout,
+            linear_sampler,
+            blur_source: shaders.blur.clone(),
+            blur_pipeline_layout,
+            globals,
+            clear_source: shaders.clear.clone(),
+            clear_pipeline_layout,
+            variants: HashMap::new(),
+            cache: PipelineCache::load(gpu_ctx, shaders),
+        }
+    }
+
+    /// Builds the pipelines for `config` unless they already exist, through
+    /// the on-disk pipeline cache when the backend supports one.
+    pub fn prepare(&mut self, device: &wgpu::Device, config: PipelineConfig) {
+        self.prepare_with(device, config, |_, create| create());
+    }
+
+    /// Like [`Pipelines::prepare`], but catches wgpu's validation errors
+    /// instead of leaving them to the device, and reports the entry file of
+    /// the first pipeline that failed. The variant isn't kept in that case.
+    pub fn try_prepare(
+        &mut self,
+        device: &wgpu::Device,
+        config: PipelineConfig,
+    ) -> Result<(), PipelineError> {
+        let mut error = None;
+        self.prepare_with(device, config, |entry, create| {
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let pipeline = create();
+            if let Some(source) = block_on(device.pop_error_scope()) {
+                error.get_or_insert_with(|| PipelineError {
+                    entry: entry.to_string(),
+                    source,
+                });
+            }
+            pipeline
+        });
+        match error {
+            Some(error) => {
+                self.variants.remove(&config);
+                Err(error)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Builds every pipeline of the variant through `create`, which is given
+    /// the entry file of the pipeline and a function building it.
+    fn prepare_with(
+        &mut self,
+        device: &wgpu::Device,
+        config: PipelineConfig,
+        mut create: impl FnMut(&str, &dyn Fn() -> wgpu::RenderPipeline) -> wgpu::RenderPipeline,
+    ) {
+        if self.variants.contains_key(&config) {
+            return;
         }
+
+        let cache = self.cache.as_ref().map(PipelineCache::cache);
+        let blur = |fragment_entry_point| {
+            create_blur_pipeline(
+                device,
+                &self.blur_pipeline_layout,
+                &self.blur_source,
+                fragment_entry_point,
+                &config,
+                cache,
+            )
+        };
+        let variant = PipelineVariant {
+            quads: create("quad.wgsl", &|| {
+                self.quads.create_pipeline(device, &config, cache)
+            }),
+            paths: create("path.wgsl", &|| {
+                self.paths.create_pipeline(device, &config, cache)
+            }),
+            underlines: create("underline.wgsl", &|| {
+                self.underlines.create_pipeline(device, &config, cache)
+            }),
+            backdrop_blurs: create("backdrop_blur.wgsl", &|| {
+                self.backdrop_blurs.create_pipeline(device, &config, cache)
+            }),
+            opacity_layers: create("opacity_layer.wgsl", &|| {
+                self.opacity_layers.create_pipeline(device, &config, cache)
+            }),
+            blur_down: create("blur.wgsl", &|| blur("fs_blur_down")),
+            blur_up: create("blur.wgsl", &|| blur("fs_blur_up")),
+            clear: create("clear.wgsl", &|| {
+                create_clear_pipeline(
+                    device,
+                    &self.clear_pipeline_layout,
+                    &self.clear_source,
+                    &config,
+                    cache,
+                )
+            }),
+        };
+        self.variants.insert(config, variant);
+        if let Some(cache) = &mut self.cache {
+            cache.mark_modified();
+        }
+    }
+
+    /// The pipelines for `config`, which must have been [prepared](Self::prepare).
+    pub fn variant(&self, config: &PipelineConfig) -> &PipelineVariant {
+        &self.variants[config]
     }
 }
+
+fn create_clear_pipeline(
+    device: &wgpu::Device,
+    pipeline_layout: &wgpu::PipelineLayout,
+    shader_source: &str,
+    config: &PipelineConfig,
+    cache: Option<&wgpu::PipelineCache>,
+) -> wgpu::RenderPipeline {
+    let shader = &device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("clear shader"),
+        source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+    });
+    let replace_with_constant = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Constant,
+        dst_factor: wgpu::BlendFactor::Zero,
+        operation: wgpu::BlendOperation::Add,
+    };
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("clear pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_clear"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_clear"),
+            targets: &[Some(wgpu::ColorTargetState {
+                write_mask: wgpu::ColorWrites::default(),
+                format: config.format,
+                blend: Some(wgpu::BlendState {
+                    color: replace_with_constant,
+                    alpha: replace_with_constant,
+                }),
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: config.sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache,
+    })
+}
+
+fn create_blur_pipeline(
+    device: &wgpu::Device,
+    pipeline_layout: &wgpu::PipelineLayout,
+    shader_source: &str,
+    fragment_entry_point: &str,
+    config: &PipelineConfig,
+    cache: Option<&wgpu::PipelineCache>,
+) -> wgpu::RenderPipeline {
+    let shader = &device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("blur shader"),
+        source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("blur pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_blur"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(fragment_entry_point),
+            targets: &[Some(wgpu::ColorTargetState {
+                write_mask: wgpu::ColorWrites::default(),
+                format: config.format,
+                blend: None,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
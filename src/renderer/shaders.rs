@@ -0,0 +1,259 @@
+use std::{
+    ffi::OsString,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+mod preprocessor;
+
+/// Tints the full instance rect of every quad, showing the area it rasterizes
+/// beyond its rounded shape.
+pub const DEBUG_QUAD_BOUNDS: &str = "DEBUG_QUAD_BOUNDS";
+
+/// The shader files compiled into the binary.
+const EMBEDDED_FILES: &[(&str, &str)] = &[
+    ("common.wgsl", include_str!("shaders/common.wgsl")),
+    ("clear.wgsl", include_str!("shaders/clear.wgsl")),
+    ("quad.wgsl", include_str!("shaders/quad.wgsl")),
+    ("path.wgsl", include_str!("shaders/path.wgsl")),
+    ("underline.wgsl", include_str!("shaders/underline.wgsl")),
+    ("kawase.wgsl", include_str!("shaders/kawase.wgsl")),
+    ("blur.wgsl", include_str!("shaders/blur.wgsl")),
+    (
+        "backdrop_blur.wgsl",
+        include_str!("shaders/backdrop_blur.wgsl"),
+    ),
+    (
+        "opacity_layer.wgsl",
+        include_str!("shaders/opacity_layer.wgsl"),
+    ),
+];
+
+/// Where shader files are loaded from.
+pub(crate) enum ShaderFiles {
+    Embedded,
+    /// Dev mode: read from a directory on disk, usually `src/renderer/shaders`.
+    Directory(PathBuf),
+}
+
+impl ShaderFiles {
+    fn load(&self, file: &str) -> Option<String> {
+        match self {
+            ShaderFiles::Embedded => EMBEDDED_FILES
+                .iter()
+                .find(|(name, _)| *name == file)
+                .map(|(_, source)| source.to_string()),
+            ShaderFiles::Directory(dir) => fs::read_to_string(dir.join(file)).ok(),
+        }
+    }
+
+    pub fn path(&self, file: &str) -> PathBuf {
+        match self {
+            ShaderFiles::Embedded => PathBuf::from(file),
+            ShaderFiles::Directory(dir) => dir.join(file),
+        }
+    }
+}
+
+/// Preprocessed WGSL for every pipeline, one module per primitive.
+pub(crate) struct Shaders {
+    pub clear: String,
+    pub quad: String,
+    pub path: String,
+    pub underline: String,
+    /// The downsample and upsample passes of backdrop blurs.
+    pub blur: String,
+    pub backdrop_blur: String,
+    pub opacity_layer: String,
+    /// A hash of where the sources were loaded from and the defines they were
+    /// compiled with, but not of their contents.
+    pub variant: u64,
+}
+
+impl Shaders {
+    /// Preprocesses and validates every entry file with the given defines.
+    pub fn compile(files: &ShaderFiles, defines: &[&str]) -> Result<Self, ShaderError> {
+        Ok(Self {
+            clear: compile(files, "clear.wgsl", defines)?,
+            quad: compile(files, "quad.wgsl", defines)?,
+            path: compile(files, "path.wgsl", defines)?,
+            underline: compile(files, "underline.wgsl", defines)?,
+            blur: compile(files, "blur.wgsl", defines)?,
+            backdrop_blur: compile(files, "backdrop_blur.wgsl", defines)?,
+            opacity_layer: compile(files, "opacity_layer.wgsl", defines)?,
+            // The directory in dev mode, empty for the embedded files.
+            variant: fnv1a(
+                [files.path("").to_string_lossy().as_ref()]
+                    .into_iter()
+                    .chain(defines.iter().copied())
+                    .flat_map(|part| part.bytes().chain([0])),
+            ),
+        })
+    }
+
+    /// A hash of all sources, stable across builds and Rust versions (FNV-1a),
+    /// so it can key on-disk caches.
+    pub fn hash(&self) -> u64 {
+        fnv1a(
+            [
+                &self.clear,
+                &self.quad,
+                &self.path,
+                &self.underline,
+                &self.blur,
+                &self.backdrop_blur,
+                &self.opacity_layer,
+            ]
+            .iter()
+            .flat_map(|source| source.bytes().chain([0])),
+        )
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across builds.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// A WGSL source that failed to preprocess, parse or validate.
+#[derive(Clone, Debug)]
+pub struct ShaderError {
+    /// The file containing the error, after resolving includes.
+    pub path: PathBuf,
+    /// 1-based line and column of the first error in `path`, if known.
+    pub location: Option<(u32, u32)>,
+    /// naga's diagnostic, with the offending source lines.
+    pub diagnostic: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.diagnostic)?;
+        if let Some((line, column)) = self.location {
+            write!(f, "\n  = in {}:{line}:{column}", self.path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Resolves the includes of `entry`, then parses and validates the result with
+/// naga, so errors are reported with their location instead of wgpu panicking
+/// on them.
+fn compile(files: &ShaderFiles, entry: &str, defines: &[&str]) -> Result<String, ShaderError> {
+    let load = |file: &str| files.load(file);
+    let (source, source_map) =
+        preprocessor::preprocess(entry, defines, &load).map_err(|error| ShaderError {
+            path: files.path(&error.file),
+            location: Some((error.line, 1)),
+            diagnostic: format!("error: {}", error.message),
+        })?;
+
+    // naga reports positions in the preprocessed source; map them back to the
+    // file they were included from.
+    let error = |location: Option<naga::SourceLocation>, diagnostic: String| {
+        let (path, location) = location
+            .and_then(|location| {
+                let (file, line) = source_map.locate(location.line_number)?;
+                Some((files.path(file), Some((line, location.line_position))))
+            })
+            .unwrap_or_else(|| (files.path(entry), None));
+        ShaderError {
+            path,
+            location,
+            diagnostic,
+        }
+    };
+    let label = format!("{entry} (preprocessed)");
+
+    let module = naga::front::wgsl::parse_str(&source).map_err(|parse_error| {
+        error(
+            parse_error.location(&source),
+            parse_error.emit_to_string_with_path(&source, &label),
+        )
+    })?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|validation_error| {
+        error(
+            validation_error.location(&source),
+            validation_error.emit_to_string_with_path(&source, &label),
+        )
+    })?;
+
+    Ok(source)
+}
+
+/// Opt-in dev mode: polls a directory of WGSL files so shaders can be edited
+/// while the renderer is running.
+pub(crate) struct ShaderWatcher {
+    dir: PathBuf,
+    /// Every file in `dir` with its modification time, sorted by name.
+    files: Option<Vec<(OsString, SystemTime)>>,
+}
+
+impl ShaderWatcher {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, files: None }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns true if a shader file was changed, added, removed or renamed
+    /// since the last call. The first call always returns true.
+    pub fn poll(&mut self) -> bool {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return false;
+        };
+        let mut files = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                Some((entry.file_name(), entry.metadata().ok()?.modified().ok()?))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        if self.files.as_ref() == Some(&files) {
+            return false;
+        }
+        self.files = Some(files);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_watcher_sees_removed_and_renamed_files() {
+        let dir = env::temp_dir().join(format!("wui-watcher-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.wgsl"), "").unwrap();
+        fs::write(dir.join("b.wgsl"), "").unwrap();
+
+        let mut watcher = ShaderWatcher::new(dir.clone());
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        // Neither changes the newest modification time, that of `b.wgsl`.
+        fs::remove_file(dir.join("a.wgsl")).unwrap();
+        assert!(watcher.poll());
+        fs::rename(dir.join("b.wgsl"), dir.join("c.wgsl")).unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
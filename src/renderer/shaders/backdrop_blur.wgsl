@@ -0,0 +1,58 @@
+#include "common.wgsl"
+#include "kawase.wgsl"
+
+struct BackdropBlur {
+  order: u32,
+  blur_radius: f32,
+  bounds: Bounds,
+  corner_radii: Corners,
+}
+
+@group(1)
+@binding(0)
+var<storage, read> b_backdrop_blurs: array<BackdropBlur>;
+
+// The first level of the blur chain, half the size of the render target. The
+// last upsample happens while drawing, straight into the target.
+@group(2)
+@binding(0)
+var t_blurred: texture_2d<f32>;
+@group(2)
+@binding(1)
+var s_blurred: sampler;
+
+struct BackdropBlurVarying {
+  @builtin(position) position: vec4<f32>,
+  @location(0) @interpolate(flat) backdrop_blur_id: u32,
+}
+
+@vertex
+fn vs_backdrop_blur(@builtin(vertex_index) vertex_id: u32, @builtin(instance_index) instance_id: u32) -> BackdropBlurVarying {
+  let unit_vertex = vec2<f32>(f32(vertex_id & 1u), 0.5 * f32(vertex_id & 2u));
+  let backdrop_blur = b_backdrop_blurs[instance_id];
+
+  var out = BackdropBlurVarying();
+  out.position = to_device_position(unit_vertex, backdrop_blur.bounds);
+  out.backdrop_blur_id = instance_id;
+  return out;
+}
+
+@fragment
+fn fs_backdrop_blur(input: BackdropBlurVarying) -> @location(0) vec4<f32> {
+  let backdrop_blur = b_backdrop_blurs[input.backdrop_blur_id];
+  let half_size = backdrop_blur.bounds.size / 2.0;
+  let point = input.position.xy - backdrop_blur.bounds.origin - half_size;
+  let radius = pick_corner_radius(point, backdrop_blur.corner_radii);
+  let coverage = saturate(0.5 - rounded_rect_distance(point, half_size, radius));
+
+  // Already encoded and premultiplied like the target, so only the coverage
+  // is applied.
+  let color = kawase_upsample(
+    t_blurred,
+    s_blurred,
+    input.position.xy / globals.viewport_size,
+    0.5 / globals.viewport_size,
+  );
+  let multiplier = select(1.0, coverage, globals.premultiplied_alpha != 0u);
+  return vec4<f32>(color.rgb * multiplier, color.a * coverage);
+}
@@ -0,0 +1,34 @@
+#include "common.wgsl"
+#include "kawase.wgsl"
+
+// One level of the blur chain of a backdrop blur, sampled to produce the next.
+// Colors are copied from the render target and written back as they are, so
+// they're never decoded, encoded or premultiplied here.
+@group(1)
+@binding(0)
+var t_source: texture_2d<f32>;
+@group(1)
+@binding(1)
+var s_source: sampler;
+
+// A triangle covering the target; the scissor rect limits it to the region
+// being blurred.
+@vertex
+fn vs_blur(@builtin(vertex_index) vertex_id: u32) -> @builtin(position) vec4<f32> {
+  let unit_vertex = vec2<f32>(f32((vertex_id << 1u) & 2u), f32(vertex_id & 2u));
+  return vec4<f32>(unit_vertex * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+// Renders to a level half the size of the source.
+@fragment
+fn fs_blur_down(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+  let target_size = max(vec2<f32>(textureDimensions(t_source) / 2u), vec2<f32>(1.0));
+  return kawase_downsample(t_source, s_source, position.xy / target_size, 0.5 / target_size);
+}
+
+// Renders to a level twice the size of the source.
+@fragment
+fn fs_blur_up(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+  let target_size = vec2<f32>(textureDimensions(t_source) * 2u);
+  return kawase_upsample(t_source, s_source, position.xy / target_size, 0.5 / target_size);
+}
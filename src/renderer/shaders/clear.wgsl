@@ -0,0 +1,12 @@
+// Fills the scissor rect with the blend constant, used to clear damaged regions
+// without discarding the rest of the previous frame.
+@vertex
+fn vs_clear(@builtin(vertex_index) vertex_id: u32) -> @builtin(position) vec4<f32> {
+  let unit_vertex = vec2<f32>(f32((vertex_id << 1u) & 2u), f32(vertex_id & 2u));
+  return vec4<f32>(unit_vertex * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+@fragment
+fn fs_clear() -> @location(0) vec4<f32> {
+  return vec4<f32>(1.0);
+}
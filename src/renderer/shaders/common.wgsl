@@ -0,0 +1,255 @@
+struct GlobalParams {
+  viewport_size: vec2<f32>,
+  premultiplied_alpha: u32,
+}
+
+@group(0)
+@binding(0)
+var<uniform> globals: GlobalParams;
+
+// Set when the target format stores colors as is instead of encoding them to
+// sRGB, e.g. `Bgra8Unorm`, so `blend_color` has to encode them itself.
+override encode_srgb: bool = false;
+
+const PI: f32 = 3.1415926535897932384626433832795;
+
+struct Bounds {
+  origin: vec2<f32>,
+  size: vec2<f32>,
+}
+
+// The horizontal and vertical radius of each corner.
+struct Corners {
+  top_left: vec2<f32>,
+  top_right: vec2<f32>,
+  bottom_right: vec2<f32>,
+  bottom_left: vec2<f32>,
+}
+
+struct Edges {
+  top: f32,
+  right: f32,
+  bottom: f32,
+  left: f32,
+}
+
+// Maps `p` to `rotation_scale * p + translation`.
+struct TransformationMatrix {
+  rotation_scale: mat2x2<f32>,
+  translation: vec2<f32>,
+}
+
+struct Hsla {
+  h: f32,
+  s: f32,
+  l: f32,
+  a: f32,
+}
+
+struct EdgeColors {
+  top: Hsla,
+  right: Hsla,
+  bottom: Hsla,
+  left: Hsla,
+}
+
+struct ColorStop {
+  color: Hsla,
+  percentage: f32,
+}
+
+const BACKGROUND_SOLID: u32 = 0u;
+const BACKGROUND_LINEAR_GRADIENT: u32 = 1u;
+const BACKGROUND_RADIAL_GRADIENT: u32 = 2u;
+
+const COLOR_SPACE_SRGB: u32 = 0u;
+const COLOR_SPACE_LINEAR_SRGB: u32 = 1u;
+const COLOR_SPACE_OKLAB: u32 = 2u;
+
+// Gradients refer to their stops in the quad shader's `b_gradient_stops` by
+// offset and count.
+struct Background {
+  tag: u32,
+  color_space: u32,
+  angle: f32,
+  stop_offset: u32,
+  center: vec2<f32>,
+  stop_count: u32,
+  solid: Hsla,
+}
+
+fn to_device_position_impl(position: vec2<f32>) -> vec4<f32> {
+  let to_device_position = position / globals.viewport_size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+  return vec4<f32>(to_device_position, 0.0, 1.0);
+}
+
+fn transform_position(position: vec2<f32>, transformation: TransformationMatrix) -> vec2<f32> {
+  return transformation.rotation_scale * position + transformation.translation;
+}
+
+fn to_device_position(unit_vertex: vec2<f32>, bounds: Bounds) -> vec4<f32> {
+  let position = unit_vertex * vec2<f32>(bounds.size) + bounds.origin;
+  return to_device_position_impl(position);
+}
+
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+  let cutoff = srgb < vec3<f32>(0.04045);
+  let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+  let lower = srgb / vec3<f32>(12.92);
+  return select(higher, lower, cutoff);
+}
+
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+  let cutoff = linear < vec3<f32>(0.0031308);
+  let higher = vec3<f32>(1.055) * pow(linear, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+  let lower = linear * vec3<f32>(12.92);
+  return select(higher, lower, cutoff);
+}
+
+fn hsla_to_rgba(hsla: Hsla) -> vec4<f32> {
+  let h = hsla.h * 6.0;
+  let s = hsla.s;
+  let l = hsla.l;
+  let a = hsla.a;
+
+  let c = (1.0 - abs(2.0 * l - 1.0)) * s;
+  let x = c * (1.0 - abs(h % 2.0 -1.0));
+  let m = l - c / 2.0;
+  var color = vec3<f32>(m);
+
+  if h >= 0.0 && h < 1.0 {
+    color.r += c;
+    color.g += x;
+  } else if h >= 1.0 && h < 2.0 {
+    color.r += x;
+    color.g += c;
+  } else if h >= 2.0 && h < 3.0 {
+    color.g += c;
+    color.b += x;
+  } else if h >= 3.0 && h < 4.0 {
+    color.g += x;
+    color.b += c;
+  } else if h >= 4.0 && h < 5.0 {
+    color.r += x;
+    color.b += c;
+  } else {
+    color.r += c;
+    color.b += x;
+  }
+
+  let linear = srgb_to_linear(color);
+  return vec4<f32>(linear, a);
+}
+
+fn over(below: vec4<f32>, above: vec4<f32>) -> vec4<f32> {
+  let alpha = above.a + below.a * (1.0 - above.a);
+  let color = (above.rgb * above.a + below.rgb * below.a * (1.0 - above.a)) / alpha;
+  return vec4<f32>(color, alpha);
+}
+
+fn blend_color(color: vec4<f32>, alpha_factor: f32) -> vec4<f32> {
+  let alpha = color.a * alpha_factor;
+  let multiplier = select(1.0, alpha, globals.premultiplied_alpha != 0u);
+  var rgb = color.rgb;
+  if encode_srgb {
+    rgb = linear_to_srgb(rgb);
+  }
+  return vec4<f32>(rgb * multiplier, alpha);
+}
+
+fn linear_to_oklab(linear: vec3<f32>) -> vec3<f32> {
+  let lms = mat3x3<f32>(
+    0.4122214708, 0.2119034982, 0.0883024619,
+    0.5363325363, 0.6806995451, 0.2817188376,
+    0.0514459929, 0.1073969566, 0.6299787005,
+  ) * linear;
+  let lms_cbrt = sign(lms) * pow(abs(lms), vec3<f32>(1.0 / 3.0));
+  return mat3x3<f32>(
+    0.2104542553, 1.9779984951, 0.0259040371,
+    0.7936177850, -2.4285922050, 0.7827717662,
+    -0.0040720468, 0.4505937099, -0.8086757660,
+  ) * lms_cbrt;
+}
+
+fn oklab_to_linear(oklab: vec3<f32>) -> vec3<f32> {
+  let lms_cbrt = mat3x3<f32>(
+    1.0, 1.0, 1.0,
+    0.3963377774, -0.1055613458, -0.0894841775,
+    0.2158037573, -0.0638541728, -1.2914855480,
+  ) * oklab;
+  let lms = lms_cbrt * lms_cbrt * lms_cbrt;
+  return mat3x3<f32>(
+    4.0767416621, -1.2684380046, -0.0041960863,
+    -3.3077115913, 2.6097574011, -0.7034186147,
+    0.2309699292, -0.3413193965, 1.7076147010,
+  ) * lms;
+}
+
+fn to_color_space(linear: vec3<f32>, color_space: u32) -> vec3<f32> {
+  switch color_space {
+    case COLOR_SPACE_LINEAR_SRGB: { return linear; }
+    case COLOR_SPACE_OKLAB: { return linear_to_oklab(linear); }
+    default: { return linear_to_srgb(linear); }
+  }
+}
+
+fn from_color_space(color: vec3<f32>, color_space: u32) -> vec3<f32> {
+  switch color_space {
+    case COLOR_SPACE_LINEAR_SRGB: { return color; }
+    case COLOR_SPACE_OKLAB: { return oklab_to_linear(color); }
+    default: { return srgb_to_linear(color); }
+  }
+}
+
+// Mixes two stops in `color_space` with premultiplied alpha, like CSS, so a
+// transparent stop fades the other color out instead of darkening it.
+fn mix_color_stops(start: Hsla, end: Hsla, t: f32, color_space: u32) -> vec4<f32> {
+  let start_rgba = hsla_to_rgba(start);
+  let end_rgba = hsla_to_rgba(end);
+  let mixed = mix(
+    vec4<f32>(to_color_space(start_rgba.rgb, color_space) * start_rgba.a, start_rgba.a),
+    vec4<f32>(to_color_space(end_rgba.rgb, color_space) * end_rgba.a, end_rgba.a),
+    t,
+  );
+  if mixed.a == 0.0 {
+    return vec4<f32>(0.0);
+  }
+  return vec4<f32>(from_color_space(mixed.rgb / mixed.a, color_space), mixed.a);
+}
+
+fn pick_corner_radius(point: vec2<f32>, radii: Corners) -> vec2<f32> {
+  var radius: vec2<f32>;
+  if point.x < 0.0 {
+    if point.y < 0.0 {
+      radius = radii.top_left;
+    } else {
+      radius = radii.bottom_left;
+    }
+  } else {
+    if point.y < 0.0 {
+      radius = radii.top_right;
+    } else {
+      radius = radii.bottom_right;
+    }
+  }
+  return sharpen_corner(radius);
+}
+
+// A corner with either radius at zero is square.
+fn sharpen_corner(radius: vec2<f32>) -> vec2<f32> {
+  return select(vec2<f32>(0.0), radius, all(radius > vec2<f32>(0.0)));
+}
+
+// The signed distance from `point`, relative to the center, to a rounded rect
+// whose nearest corner has `radius`, negative inside. Elliptical corners are
+// approximated by dividing by the gradient, exact on the outline itself.
+fn rounded_rect_distance(point: vec2<f32>, half_size: vec2<f32>, radius: vec2<f32>) -> f32 {
+  let corner_to_point = abs(point) - half_size + radius;
+  if all(corner_to_point > vec2<f32>(0.0)) && all(radius > vec2<f32>(0.0)) {
+    let scaled = corner_to_point / radius;
+    let gradient = length(corner_to_point / (radius * radius));
+    return (length(scaled) - 1.0) * length(scaled) / gradient;
+  }
+  let edge_to_point = abs(point) - half_size;
+  return length(max(vec2<f32>(0.0), edge_to_point)) + min(0.0, max(edge_to_point.x, edge_to_point.y));
+}
@@ -0,0 +1,25 @@
+// The dual Kawase blur filters, from Marius Bjørge's "Bandwidth-Efficient
+// Rendering" (SIGGRAPH 2015). Each downsample halves the resolution and each
+// upsample doubles it, so a few passes blur as widely as a large Gaussian.
+// `half_pixel` is half a texel of the texture being rendered to.
+
+fn kawase_downsample(source: texture_2d<f32>, source_sampler: sampler, uv: vec2<f32>, half_pixel: vec2<f32>) -> vec4<f32> {
+  var sum = textureSample(source, source_sampler, uv) * 4.0;
+  sum += textureSample(source, source_sampler, uv - half_pixel);
+  sum += textureSample(source, source_sampler, uv + half_pixel);
+  sum += textureSample(source, source_sampler, uv + vec2<f32>(half_pixel.x, -half_pixel.y));
+  sum += textureSample(source, source_sampler, uv - vec2<f32>(half_pixel.x, -half_pixel.y));
+  return sum / 8.0;
+}
+
+fn kawase_upsample(source: texture_2d<f32>, source_sampler: sampler, uv: vec2<f32>, half_pixel: vec2<f32>) -> vec4<f32> {
+  var sum = textureSample(source, source_sampler, uv + vec2<f32>(-half_pixel.x * 2.0, 0.0));
+  sum += textureSample(source, source_sampler, uv + vec2<f32>(-half_pixel.x, half_pixel.y)) * 2.0;
+  sum += textureSample(source, source_sampler, uv + vec2<f32>(0.0, half_pixel.y * 2.0));
+  sum += textureSample(source, source_sampler, uv + vec2<f32>(half_pixel.x, half_pixel.y)) * 2.0;
+  sum += textureSample(source, source_sampler, uv + vec2<f32>(half_pixel.x * 2.0, 0.0));
+  sum += textureSample(source, source_sampler, uv + vec2<f32>(half_pixel.x, -half_pixel.y)) * 2.0;
+  sum += textureSample(source, source_sampler, uv + vec2<f32>(0.0, -half_pixel.y * 2.0));
+  sum += textureSample(source, source_sampler, uv + vec2<f32>(-half_pixel.x, -half_pixel.y)) * 2.0;
+  return sum / 12.0;
+}
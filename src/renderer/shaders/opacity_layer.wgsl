@@ -0,0 +1,46 @@
+#include "common.wgsl"
+
+struct LayerComposite {
+  bounds: Bounds,
+  opacity: f32,
+}
+
+@group(1)
+@binding(0)
+var<storage, read> b_opacity_layers: array<LayerComposite>;
+
+// What was drawn in the layer, the size of the render target and at the same
+// position, so it's loaded texel for pixel rather than sampled.
+@group(2)
+@binding(0)
+var t_layer: texture_2d<f32>;
+
+struct OpacityLayerVarying {
+  @builtin(position) position: vec4<f32>,
+  @location(0) @interpolate(flat) opacity_layer_id: u32,
+}
+
+@vertex
+fn vs_opacity_layer(@builtin(vertex_index) vertex_id: u32, @builtin(instance_index) instance_id: u32) -> OpacityLayerVarying {
+  let unit_vertex = vec2<f32>(f32(vertex_id & 1u), 0.5 * f32(vertex_id & 2u));
+  let layer = b_opacity_layers[instance_id];
+
+  var out = OpacityLayerVarying();
+  out.position = to_device_position(unit_vertex, layer.bounds);
+  out.opacity_layer_id = instance_id;
+  return out;
+}
+
+@fragment
+fn fs_opacity_layer(input: OpacityLayerVarying) -> @location(0) vec4<f32> {
+  let layer = b_opacity_layers[input.opacity_layer_id];
+  // Already encoded like the target. Blending over the transparent clear
+  // premultiplies it even with straight alpha pipelines, so that's undone for
+  // them.
+  let color = textureLoad(t_layer, vec2<i32>(input.position.xy), 0);
+  if globals.premultiplied_alpha != 0u {
+    return color * layer.opacity;
+  }
+  let rgb = select(vec3<f32>(0.0), color.rgb / color.a, color.a > 0.0);
+  return vec4<f32>(rgb, color.a * layer.opacity);
+}
@@ -0,0 +1,35 @@
+#include "common.wgsl"
+
+// A path vertex along with its path's transformation.
+struct TransformedPathVertex {
+  xy_position: vec2<f32>,
+  color: Hsla,
+  transformation: TransformationMatrix,
+}
+
+@group(1)
+@binding(0)
+var<storage, read> b_path_vertices: array<TransformedPathVertex>;
+
+struct PathVarying {
+  @builtin(position) position: vec4<f32>,
+  @location(0) @interpolate(flat) color: vec4<f32>,
+}
+
+// Paths are tessellated on the CPU, so every vertex is drawn as is, three per
+// triangle. Their edges are only anti-aliased by MSAA.
+@vertex
+fn vs_path(@builtin(vertex_index) vertex_id: u32) -> PathVarying {
+  let vertex = b_path_vertices[vertex_id];
+  let position = transform_position(vertex.xy_position, vertex.transformation);
+
+  var out = PathVarying();
+  out.position = to_device_position_impl(position);
+  out.color = hsla_to_rgba(vertex.color);
+  return out;
+}
+
+@fragment
+fn fs_path(input: PathVarying) -> @location(0) vec4<f32> {
+  return blend_color(input.color, 1.0);
+}
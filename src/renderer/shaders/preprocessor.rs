@@ -0,0 +1,205 @@
+//! A tiny WGSL preprocessor, so each primitive's shader can live in its own
+//! file while sharing a common prelude. Also compiled into `build.rs`, so it
+//! must only depend on `std`.
+//!
+//! Supported directives, each on its own line:
+//!
+//! - `#include "file.wgsl"` pastes another file, at most once per output.
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep or drop lines
+//!   depending on the defines passed to [`preprocess`].
+
+use std::{collections::HashSet, fmt};
+
+/// Maps every line of the preprocessed output back to where it came from.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    lines: Vec<(String, u32)>,
+}
+
+impl SourceMap {
+    /// Returns the file and 1-based line of the 1-based output `line`.
+    pub fn locate(&self, line: u32) -> Option<(&str, u32)> {
+        let (file, line) = self.lines.get(line.checked_sub(1)? as usize)?;
+        Some((file, *line))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PreprocessError {
+    pub file: String,
+    pub line: u32,
+    pub message: String,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+/// Resolves the directives in `entry` and everything it includes, loading
+/// files by name through `load`.
+pub fn preprocess(
+    entry: &str,
+    defines: &[&str],
+    load: &dyn Fn(&str) -> Option<String>,
+) -> Result<(String, SourceMap), PreprocessError> {
+    let mut preprocessor = Preprocessor {
+        defines,
+        load,
+        included: HashSet::new(),
+        output: String::new(),
+        source_map: SourceMap::default(),
+    };
+    preprocessor.include(entry, None)?;
+    Ok((preprocessor.output, preprocessor.source_map))
+}
+
+struct Preprocessor<'a> {
+    defines: &'a [&'a str],
+    load: &'a dyn Fn(&str) -> Option<String>,
+    included: HashSet<String>,
+    output: String,
+    source_map: SourceMap,
+}
+
+/// An open `#ifdef`/`#ifndef` block.
+struct Conditional {
+    active: bool,
+    seen_else: bool,
+}
+
+impl Preprocessor<'_> {
+    fn include(&mut self, file: &str, from: Option<(&str, u32)>) -> Result<(), PreprocessError> {
+        if !self.included.insert(file.to_string()) {
+            return Ok(());
+        }
+
+        let source = (self.load)(file).ok_or_else(|| {
+            let (from_file, from_line) = from.unwrap_or((file, 0));
+            PreprocessError {
+                file: from_file.to_string(),
+                line: from_line,
+                message: format!("cannot find shader file `{file}`"),
+            }
+        })?;
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (ix, line) in source.lines().enumerate() {
+            let line_number = ix as u32 + 1;
+            let error = |message: String| PreprocessError {
+                file: file.to_string(),
+                line: line_number,
+                message,
+            };
+            let active = conditionals.iter().all(|conditional| conditional.active);
+
+            let Some(directive) = line.trim().strip_prefix('#') else {
+                if active {
+                    self.output.push_str(line);
+                    self.output.push('\n');
+                    self.source_map.lines.push((file.to_string(), line_number));
+                }
+                continue;
+            };
+
+            let mut parts = directive.split_whitespace();
+            let name = parts.next().unwrap_or_default();
+            let argument = parts.next();
+            match (name, argument) {
+                ("include", Some(argument)) => {
+                    let included = argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .ok_or_else(|| {
+                            error(format!("expected a quoted file name, found {argument}"))
+                        })?;
+                    if active {
+                        self.include(included, Some((file, line_number)))?;
+                    }
+                }
+                ("ifdef" | "ifndef", Some(define)) => {
+                    let defined = self.defines.contains(&define);
+                    conditionals.push(Conditional {
+                        active: defined == (name == "ifdef"),
+                        seen_else: false,
+                    });
+                }
+                ("else", None) => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.seen_else => {
+                        conditional.active = !conditional.active;
+                        conditional.seen_else = true;
+                    }
+                    _ => return Err(error("unexpected #else".into())),
+                },
+                ("endif", None) => {
+                    if conditionals.pop().is_none() {
+                        return Err(error("unexpected #endif".into()));
+                    }
+                }
+                _ => return Err(error(format!("invalid directive `#{directive}`"))),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            return Err(PreprocessError {
+                file: file.to_string(),
+                line: source.lines().count() as u32,
+                message: "missing #endif".into(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file: &str) -> Option<String> {
+        match file {
+            "common.wgsl" => Some("fn common() {}\n".into()),
+            "a.wgsl" => Some("#include \"common.wgsl\"\nfn a() {}\n".into()),
+            "main.wgsl" => Some(
+                "#include \"common.wgsl\"\n#include \"a.wgsl\"\n#ifdef DEBUG\nfn debug() {}\n#else\nfn release() {}\n#endif\nfn main() {}\n"
+                    .into(),
+            ),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_includes_each_file_once() {
+        let (output, source_map) = preprocess("main.wgsl", &[], &load).unwrap();
+
+        assert_eq!(
+            output,
+            "fn common() {}\nfn a() {}\nfn release() {}\nfn main() {}\n"
+        );
+        assert_eq!(source_map.locate(1), Some(("common.wgsl", 1)));
+        assert_eq!(source_map.locate(2), Some(("a.wgsl", 2)));
+        assert_eq!(source_map.locate(4), Some(("main.wgsl", 8)));
+    }
+
+    #[test]
+    fn test_defines_select_branches() {
+        let (output, _) = preprocess("main.wgsl", &["DEBUG"], &load).unwrap();
+
+        assert!(output.contains("fn debug() {}"));
+        assert!(!output.contains("fn release() {}"));
+    }
+
+    #[test]
+    fn test_reports_errors_with_location() {
+        let load = |file: &str| match file {
+            "main.wgsl" => Some("fn main() {}\n#include \"missing.wgsl\"\n".to_string()),
+            _ => None,
+        };
+        let error = preprocess("main.wgsl", &[], &load).unwrap_err();
+        assert_eq!((error.file.as_str(), error.line), ("main.wgsl", 2));
+
+        let load = |_: &str| Some("#ifdef DEBUG\nfn debug() {}\n".to_string());
+        let error = preprocess("main.wgsl", &[], &load).unwrap_err();
+        assert_eq!(error.message, "missing #endif");
+    }
+}
@@ -0,0 +1,281 @@
+#include "common.wgsl"
+
+const BORDER_STYLE_SOLID: u32 = 0u;
+const BORDER_STYLE_DASHED: u32 = 1u;
+const BORDER_STYLE_DOTTED: u32 = 2u;
+
+struct Quad {
+  order: u32,
+  border_style: u32,
+  bounds: Bounds,
+  background: Background,
+  border_colors: EdgeColors,
+  corner_radii: Corners,
+  border_widths: Edges,
+  border_dash_length: f32,
+  border_dash_gap: f32,
+  transformation: TransformationMatrix,
+}
+
+@group(1)
+@binding(0)
+var<storage, read> b_quads: array<Quad>;
+
+@group(2)
+@binding(0)
+var<storage, read> b_gradient_stops: array<ColorStop>;
+
+struct QuadVarying {
+  @builtin(position) position: vec4<f32>,
+  @location(0) @interpolate(flat) background_color: vec4<f32>,
+  @location(1) @interpolate(flat) border_color_top: vec4<f32>,
+  @location(2) @interpolate(flat) border_color_right: vec4<f32>,
+  @location(3) @interpolate(flat) border_color_bottom: vec4<f32>,
+  @location(4) @interpolate(flat) border_color_left: vec4<f32>,
+  @location(5) @interpolate(flat) quad_id: u32,
+  // Where the fragment is before the quad's transformation, which is where
+  // its shape is computed.
+  @location(6) local_position: vec2<f32>,
+}
+
+@vertex
+fn vs_quad(@builtin(vertex_index) vertex_id: u32, @builtin(instance_index) instance_id: u32) -> QuadVarying {
+  let unit_vertex = vec2<f32>(f32(vertex_id & 1u), 0.5 * f32(vertex_id & 2u));
+  let quad = b_quads[instance_id];
+
+  let local_position = unit_vertex * quad.bounds.size + quad.bounds.origin;
+
+  var out = QuadVarying();
+  out.position = to_device_position_impl(transform_position(local_position, quad.transformation));
+  out.local_position = local_position;
+  // Gradients vary per pixel, so only solid colors are computed here.
+  out.background_color = hsla_to_rgba(quad.background.solid);
+  out.border_color_top = hsla_to_rgba(quad.border_colors.top);
+  out.border_color_right = hsla_to_rgba(quad.border_colors.right);
+  out.border_color_bottom = hsla_to_rgba(quad.border_colors.bottom);
+  out.border_color_left = hsla_to_rgba(quad.border_colors.left);
+  out.quad_id = instance_id;
+  return out;
+}
+
+@fragment
+fn fs_quad(input: QuadVarying) -> @location(0) vec4<f32> {
+  let quad = b_quads[input.quad_id];
+  var background_color = input.background_color;
+  if quad.background.tag != BACKGROUND_SOLID {
+    background_color = background_color_at(quad.background, input.local_position, quad.bounds);
+  }
+
+  let radii = quad.corner_radii;
+  let widths = quad.border_widths;
+  let max_radius = max(max(radii.top_left, radii.top_right), max(radii.bottom_right, radii.bottom_left));
+  let max_border_width = max(max(widths.top, widths.right), max(widths.bottom, widths.left));
+  // Rotated or skewed edges still need anti-aliasing.
+  let rotation_scale = quad.transformation.rotation_scale;
+  let axis_aligned = rotation_scale[0].y == 0.0 && rotation_scale[1].x == 0.0;
+  if all(max_radius == vec2<f32>(0.0)) && max_border_width == 0.0 && axis_aligned {
+    return blend_color(background_color, 1.0);
+  }
+
+  let half_size = quad.bounds.size / 2.0;
+  let center = quad.bounds.origin + half_size;
+  let center_to_point = input.local_position - center;
+
+  let corner_radius = pick_corner_radius(center_to_point, radii);
+  let distance = rounded_rect_distance(center_to_point, half_size, corner_radius);
+
+  // Adjacent sides meet on the line from the outer to the inner corner of the
+  // border box, as in CSS.
+  let vertical_border = select(widths.left, widths.right, center_to_point.x > 0.0);
+  let horizontal_border = select(widths.top, widths.bottom, center_to_point.y > 0.0);
+  let edge_to_point = half_size - abs(center_to_point);
+  let seam_distance = (edge_to_point.x * horizontal_border - edge_to_point.y * vertical_border) / max(length(vec2<f32>(vertical_border, horizontal_border)), 1e-6);
+  let border_width = select(vertical_border, horizontal_border, seam_distance > 0.0);
+
+  var color = background_color;
+  if max_border_width > 0.0 {
+    // Like CSS, the inner edge is inset by each side's width, and its corners
+    // by the widths of the sides they join.
+    let inner_center_to_point = center_to_point - vec2<f32>(widths.left - widths.right, widths.top - widths.bottom) / 2.0;
+    let inner_half_size = half_size - vec2<f32>(widths.left + widths.right, widths.top + widths.bottom) / 2.0;
+    let inner_radii = Corners(
+      max(radii.top_left - vec2<f32>(widths.left, widths.top), vec2<f32>(0.0)),
+      max(radii.top_right - vec2<f32>(widths.right, widths.top), vec2<f32>(0.0)),
+      max(radii.bottom_right - vec2<f32>(widths.right, widths.bottom), vec2<f32>(0.0)),
+      max(radii.bottom_left - vec2<f32>(widths.left, widths.bottom), vec2<f32>(0.0)),
+    );
+    let inner_distance = rounded_rect_distance(
+      inner_center_to_point,
+      inner_half_size,
+      pick_corner_radius(inner_center_to_point, inner_radii),
+    );
+
+    let horizontal = saturate(0.5 + seam_distance);
+    var border_color = mix(
+      select(input.border_color_left, input.border_color_right, center_to_point.x > 0.0),
+      select(input.border_color_top, input.border_color_bottom, center_to_point.y > 0.0),
+      horizontal,
+    );
+    if border_width > 0.0 && quad.border_style != BORDER_STYLE_SOLID {
+      border_color.a *= border_pattern_coverage(quad, center_to_point, distance, border_width);
+    }
+    if border_color.a > 0.0 {
+      let blended_border = over(background_color, border_color);
+      color = mix(background_color, blended_border, saturate(0.5 + inner_distance));
+    }
+  }
+
+  let coverage = saturate(0.5 - distance);
+#ifdef DEBUG_QUAD_BOUNDS
+  // Tint the whole instance rect, showing what each quad rasterizes beyond its shape.
+  return over(vec4<f32>(1.0, 0.0, 1.0, 0.3), blend_color(color, coverage));
+#else
+  return blend_color(color, coverage);
+#endif
+}
+
+// How much of the dash or dot pattern covers `point`, relative to the quad's
+// center, given its distance to the quad's outline and the width of the border
+// there.
+fn border_pattern_coverage(quad: Quad, point: vec2<f32>, distance: f32, border_width: f32) -> f32 {
+  // Dashes are laid out along the middle of the border. Sides of different
+  // widths share one line, inset by their average half width.
+  let widths = quad.border_widths;
+  let inset = (widths.top + widths.right + widths.bottom + widths.left) / 8.0;
+  let radii = quad.corner_radii;
+  let center_line = border_position(
+    point,
+    quad.bounds.size / 2.0 - inset,
+    Corners(
+      sharpen_corner(max(radii.top_left - inset, vec2<f32>(0.0))),
+      sharpen_corner(max(radii.top_right - inset, vec2<f32>(0.0))),
+      sharpen_corner(max(radii.bottom_right - inset, vec2<f32>(0.0))),
+      sharpen_corner(max(radii.bottom_left - inset, vec2<f32>(0.0))),
+    ),
+  );
+  let position = center_line.x;
+  let outline_length = center_line.y;
+
+  var period = quad.border_dash_gap;
+  if quad.border_style == BORDER_STYLE_DASHED {
+    period += quad.border_dash_length;
+  } else {
+    period += border_width;
+  }
+  if period <= 0.0 {
+    return 1.0;
+  }
+  // Stretch the pattern so it repeats a whole number of times, rather than
+  // leaving a partial dash where it wraps around.
+  let stretch = outline_length / max(1.0, round(outline_length / period)) / period;
+  period *= stretch;
+  let local = position - floor(position / period) * period;
+
+  if quad.border_style == BORDER_STYLE_DASHED {
+    let dash_length = quad.border_dash_length * stretch;
+    // Signed distance to this dash, or to the start of the next one.
+    let dash_distance = min(max(-local, local - dash_length), period - local);
+    return saturate(0.5 - dash_distance);
+  }
+
+  // Dots are centered on multiples of the period.
+  let along = min(local, period - local);
+  let across = distance + border_width / 2.0;
+  return saturate(0.5 - (length(vec2<f32>(along, across)) - border_width / 2.0));
+}
+
+// Where the point on a rounded rect's outline nearest to `point` is: its
+// distance along the outline, clockwise from where the top side leaves the
+// top left corner, and the length of the whole outline. Positions along
+// elliptical corners are spread by angle, which is close enough for dashes.
+fn border_position(point: vec2<f32>, half_size: vec2<f32>, radii: Corners) -> vec2<f32> {
+  let top_right = 2.0 * half_size.x - radii.top_left.x - radii.top_right.x;
+  let right = top_right + quarter_ellipse_length(radii.top_right);
+  let bottom_right = right + 2.0 * half_size.y - radii.top_right.y - radii.bottom_right.y;
+  let bottom = bottom_right + quarter_ellipse_length(radii.bottom_right);
+  let bottom_left = bottom + 2.0 * half_size.x - radii.bottom_right.x - radii.bottom_left.x;
+  let left = bottom_left + quarter_ellipse_length(radii.bottom_left);
+  let top_left = left + 2.0 * half_size.y - radii.bottom_left.y - radii.top_left.y;
+  let outline_length = top_left + quarter_ellipse_length(radii.top_left);
+
+  let h = half_size;
+  let to_length = 2.0 / PI;
+  var position: f32;
+  if point.x > h.x - radii.top_right.x && point.y < radii.top_right.y - h.y {
+    let v = (point - vec2<f32>(h.x, -h.y)) / radii.top_right + vec2<f32>(1.0, -1.0);
+    position = top_right + atan2(v.x, -v.y) * to_length * quarter_ellipse_length(radii.top_right);
+  } else if point.x > h.x - radii.bottom_right.x && point.y > h.y - radii.bottom_right.y {
+    let v = (point - h) / radii.bottom_right + vec2<f32>(1.0);
+    position = bottom_right + atan2(v.y, v.x) * to_length * quarter_ellipse_length(radii.bottom_right);
+  } else if point.x < radii.bottom_left.x - h.x && point.y > h.y - radii.bottom_left.y {
+    let v = (point - vec2<f32>(-h.x, h.y)) / radii.bottom_left + vec2<f32>(-1.0, 1.0);
+    position = bottom_left + atan2(-v.x, v.y) * to_length * quarter_ellipse_length(radii.bottom_left);
+  } else if point.x < radii.top_left.x - h.x && point.y < radii.top_left.y - h.y {
+    let v = (point + h) / radii.top_left - vec2<f32>(1.0);
+    position = top_left + atan2(-v.y, -v.x) * to_length * quarter_ellipse_length(radii.top_left);
+  } else {
+    // Between the corners, the nearest side is the nearest straight edge.
+    let to_edge = h - abs(point);
+    if to_edge.y < to_edge.x {
+      if point.y < 0.0 {
+        position = point.x + h.x - radii.top_left.x;
+      } else {
+        position = bottom + h.x - radii.bottom_right.x - point.x;
+      }
+    } else {
+      if point.x > 0.0 {
+        position = right + point.y + h.y - radii.top_right.y;
+      } else {
+        position = left + h.y - radii.bottom_left.y - point.y;
+      }
+    }
+  }
+  return vec2<f32>(position, outline_length);
+}
+
+// Ramanujan's approximation, exact for circles.
+fn quarter_ellipse_length(radius: vec2<f32>) -> f32 {
+  let sum = radius.x + radius.y;
+  return PI / 4.0 * (3.0 * sum - sqrt((3.0 * radius.x + radius.y) * (radius.x + 3.0 * radius.y)));
+}
+
+// The linear color of `background` at `position`, in the same space as
+// `hsla_to_rgba`.
+fn background_color_at(background: Background, position: vec2<f32>, bounds: Bounds) -> vec4<f32> {
+  if background.tag == BACKGROUND_SOLID {
+    return hsla_to_rgba(background.solid);
+  }
+
+  var t: f32;
+  let center = bounds.origin + bounds.size / 2.0;
+  if background.tag == BACKGROUND_LINEAR_GRADIENT {
+    // As in CSS, the gradient line is just long enough for the corners it
+    // points away from and towards to get the first and last stop.
+    let angle = radians(background.angle);
+    let direction = vec2<f32>(sin(angle), -cos(angle));
+    let half_length = dot(abs(direction), bounds.size / 2.0);
+    t = dot(position - center, direction) / (2.0 * half_length) + 0.5;
+  } else {
+    let gradient_center = bounds.origin + background.center * bounds.size;
+    let to_farthest_corner = max(
+      gradient_center - bounds.origin,
+      bounds.origin + bounds.size - gradient_center,
+    );
+    t = length(position - gradient_center) / length(to_farthest_corner);
+  }
+
+  let first = b_gradient_stops[background.stop_offset];
+  if t <= first.percentage {
+    return hsla_to_rgba(first.color);
+  }
+  for (var i = 1u; i < background.stop_count; i++) {
+    let start = b_gradient_stops[background.stop_offset + i - 1u];
+    let end = b_gradient_stops[background.stop_offset + i];
+    if t <= end.percentage {
+      let span = end.percentage - start.percentage;
+      let local_t = select(1.0, (t - start.percentage) / span, span > 0.0);
+      return mix_color_stops(start.color, end.color, local_t, background.color_space);
+    }
+  }
+  return hsla_to_rgba(b_gradient_stops[background.stop_offset + background.stop_count - 1u].color);
+}
@@ -0,0 +1,56 @@
+#include "common.wgsl"
+
+const UNDERLINE_STRAIGHT: u32 = 0u;
+const UNDERLINE_WAVY: u32 = 1u;
+
+struct Underline {
+  order: u32,
+  bounds: Bounds,
+  color: Hsla,
+  thickness: f32,
+  style: u32,
+}
+
+@group(1)
+@binding(0)
+var<storage, read> b_underlines: array<Underline>;
+
+struct UnderlineVarying {
+  @builtin(position) position: vec4<f32>,
+  @location(0) @interpolate(flat) color: vec4<f32>,
+  @location(1) @interpolate(flat) underline_id: u32,
+}
+
+@vertex
+fn vs_underline(@builtin(vertex_index) vertex_id: u32, @builtin(instance_index) instance_id: u32) -> UnderlineVarying {
+  let unit_vertex = vec2<f32>(f32(vertex_id & 1u), 0.5 * f32(vertex_id & 2u));
+  let underline = b_underlines[instance_id];
+
+  var out = UnderlineVarying();
+  out.position = to_device_position(unit_vertex, underline.bounds);
+  out.color = hsla_to_rgba(underline.color);
+  out.underline_id = instance_id;
+  return out;
+}
+
+@fragment
+fn fs_underline(input: UnderlineVarying) -> @location(0) vec4<f32> {
+  let underline = b_underlines[input.underline_id];
+  let half_thickness = underline.thickness / 2.0;
+  let center_y = underline.bounds.origin.y + underline.bounds.size.y / 2.0;
+
+  var distance = abs(input.position.y - center_y);
+  if underline.style == UNDERLINE_WAVY {
+    // A sine wave touching the top and bottom of the bounds, starting at their
+    // left edge. The vertical distance is divided by the slope to approximate
+    // the distance to the curve, keeping the stroke width even.
+    let amplitude = max(0.0, underline.bounds.size.y / 2.0 - half_thickness);
+    let frequency = PI / underline.bounds.size.y;
+    let x = (input.position.x - underline.bounds.origin.x) * frequency;
+    let slope = amplitude * frequency * cos(x);
+    distance = abs(input.position.y - center_y + amplitude * sin(x)) / sqrt(1.0 + slope * slope);
+  }
+
+  let coverage = saturate(0.5 + half_thickness - distance);
+  return blend_color(input.color, coverage);
+}
@@ -0,0 +1,123 @@
+use super::pipelines::{PipelineConfig, Pipelines};
+
+/// Offscreen render targets for opacity layers, reused across frames so that
+/// fading a popup doesn't allocate a texture every frame.
+///
+/// Textures released during a frame are handed out again by the next
+/// [`TexturePool::acquire`] for the same config and size. Those that sat
+/// unused for a whole frame are dropped by [`TexturePool::end_frame`].
+#[derive(Default)]
+pub(crate) struct TexturePool {
+    /// Released during the current frame.
+    released: Vec<PooledTexture>,
+    /// Left over from the previous frame.
+    idle: Vec<PooledTexture>,
+}
+
+/// A single sampled texture, bound for compositing, with the multisampled
+/// one rendered into and resolved to it when the config asks for MSAA.
+pub(crate) struct PooledTexture {
+    config: PipelineConfig,
+    size: wgpu::Extent3d,
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub msaa_view: Option<wgpu::TextureView>,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl PooledTexture {
+    /// The view to render into.
+    pub fn render_view(&self) -> &wgpu::TextureView {
+        self.msaa_view.as_ref().unwrap_or(&self.view)
+    }
+
+    /// Where the samples of [`PooledTexture::render_view`] are resolved to.
+    pub fn resolve_target(&self) -> Option<&wgpu::TextureView> {
+        self.msaa_view.as_ref().map(|_| &self.view)
+    }
+}
+
+impl TexturePool {
+    /// A texture for a target with `config` and `size`, reused if one was
+    /// released this frame or the last, and created otherwise. Its previous
+    /// contents are undefined.
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        pipelines: &Pipelines,
+        config: PipelineConfig,
+        size: wgpu::Extent3d,
+    ) -> PooledTexture {
+        let fits = |texture: &PooledTexture| texture.config == config && texture.size == size;
+        for list in [&mut self.released, &mut self.idle] {
+            if let Some(ix) = list.iter().position(fits) {
+                return list.swap_remove(ix);
+            }
+        }
+
+        let texture_desc = wgpu::TextureDescriptor {
+            label: Some("opacity layer texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            // Backdrop blurs within the layer copy from it.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&texture_desc);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = (config.sample_count > 1).then(|| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("opacity layer msaa texture"),
+                    sample_count: config.sample_count,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    ..texture_desc
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("opacity layer bind group"),
+            layout: &pipelines.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&pipelines.linear_sampler),
+                },
+            ],
+        });
+
+        PooledTexture {
+            config,
+            size,
+            texture,
+            view,
+            msaa_view,
+            bind_group,
+        }
+    }
+
+    /// Returns a texture once the commands reading it have been recorded.
+    pub fn release(&mut self, texture: PooledTexture) {
+        self.released.push(texture);
+    }
+
+    /// Drops the textures no frame asked for since the last call.
+    pub fn end_frame(&mut self) {
+        self.idle = std::mem::take(&mut self.released);
+    }
+
+    /// How many textures are kept for reuse.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.released.len() + self.idle.len()
+    }
+}
@@ -0,0 +1,79 @@
+use wgpu::util::StagingBelt;
+
+/// How many frames may be queued on the GPU while the CPU records the next one.
+const FRAMES_IN_FLIGHT: usize = 3;
+
+/// Size of a staging chunk. Writes larger than this get a dedicated chunk.
+const CHUNK_SIZE: wgpu::BufferAddress = 256 * 1024;
+
+/// Bytes moved through the [`UploadBelt`] during the last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UploadStats {
+    pub bytes: u64,
+    pub writes: u32,
+}
+
+/// Uploads buffer data through staging buffers that are mapped at creation,
+/// instead of `Queue::write_buffer` copying into wgpu's internal staging memory.
+///
+/// One [`StagingBelt`] is kept per frame in flight and used round robin, so the
+/// chunks written in a frame have had two more frames to be recalled before they
+/// are written again and the CPU never waits on a chunk the GPU is still reading.
+pub(crate) struct UploadBelt {
+    belts: [StagingBelt; FRAMES_IN_FLIGHT],
+    frame: usize,
+    stats: UploadStats,
+}
+
+impl UploadBelt {
+    pub fn new() -> Self {
+        Self {
+            belts: std::array::from_fn(|_| StagingBelt::new(CHUNK_SIZE)),
+            frame: 0,
+            stats: UploadStats::default(),
+        }
+    }
+
+    /// Moves on to the next belt in the ring and resets the stats.
+    pub fn begin_frame(&mut self) {
+        self.frame = (self.frame + 1) % FRAMES_IN_FLIGHT;
+        self.stats = UploadStats::default();
+    }
+
+    /// Records a copy of `data` into `target` at `offset` on `encoder`.
+    ///
+    /// `data` must be a multiple of [`wgpu::COPY_BUFFER_ALIGNMENT`] in size.
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) {
+        let Some(size) = wgpu::BufferSize::new(data.len() as u64) else {
+            return;
+        };
+        self.belts[self.frame]
+            .write_buffer(encoder, target, offset, size, device)
+            .copy_from_slice(data);
+        self.stats.bytes += size.get();
+        self.stats.writes += 1;
+    }
+
+    /// Closes this frame's chunks. Must be called before submitting the encoders
+    /// passed to [`UploadBelt::write`].
+    pub fn finish(&mut self) {
+        self.belts[self.frame].finish();
+    }
+
+    /// Hands this frame's chunks back to be remapped once the GPU is done with
+    /// them. Must be called after submitting.
+    pub fn recall(&mut self) {
+        self.belts[self.frame].recall();
+    }
+
+    pub fn stats(&self) -> UploadStats {
+        self.stats
+    }
+}
@@ -0,0 +1,171 @@
+//! Compile time checks that the `#[repr(C)]` types we upload match the memory
+//! layout of their WGSL counterparts. The WGSL side is reflected with naga by
+//! `build.rs`; a mismatch in field order, offset, size or array stride fails the
+//! build instead of silently corrupting instance data.
+
+use std::mem::{align_of, offset_of, size_of};
+
+use crate::{
+    color::{ColorStop, Hsla},
+    geometry::{Bounds, Corners, Edges, ScaledPixels, Size, TransformationMatrix},
+    scene::{BackdropBlur, Underline},
+};
+
+use super::{
+    BackgroundInstance, GlobalParams, LayerComposite, QuadInstance, TransformedPathVertex,
+};
+
+pub(crate) struct WgslStruct {
+    pub size: usize,
+    pub align: usize,
+    pub members: &'static [WgslMember],
+}
+
+pub(crate) struct WgslMember {
+    pub name: &'static str,
+    pub offset: usize,
+}
+
+impl WgslStruct {
+    /// Returns the index and offset of the member called `name`.
+    pub const fn member(&self, name: &str) -> Option<(usize, usize)> {
+        let mut i = 0;
+        while i < self.members.len() {
+            if str_eq(self.members[i].name, name) {
+                return Some((i, self.members[i].offset));
+            }
+            i += 1;
+        }
+        None
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+mod wgsl {
+    use super::{WgslMember, WgslStruct};
+
+    include!(concat!(env!("OUT_DIR"), "/wgsl_layouts.rs"));
+}
+
+/// Asserts that `$ty` has the same fields, in the same order and at the same
+/// offsets, as the WGSL struct `$wgsl`. Padding fields that only exist on the
+/// Rust side are left out of the list.
+macro_rules! assert_wgsl_layout {
+    ($ty:ty, $wgsl:expr, { $($field:ident),* $(,)? }) => {
+        const _: () = {
+            let wgsl = &$wgsl;
+            assert!(
+                size_of::<$ty>() == wgsl.size,
+                concat!("size of `", stringify!($ty), "` differs from its WGSL struct"),
+            );
+            assert!(
+                size_of::<$ty>() % wgsl.align == 0 && wgsl.align % align_of::<$ty>() == 0,
+                concat!("alignment of `", stringify!($ty), "` differs from its WGSL struct"),
+            );
+
+            let mut index = 0;
+            $(
+                match wgsl.member(stringify!($field)) {
+                    Some((member_index, offset)) => {
+                        assert!(
+                            member_index == index,
+                            concat!(
+                                "`", stringify!($ty), "::", stringify!($field),
+                                "` is declared in a different order in WGSL"
+                            ),
+                        );
+                        assert!(
+                            offset_of!($ty, $field) == offset,
+                            concat!(
+                                "`", stringify!($ty), "::", stringify!($field),
+                                "` is at a different offset in WGSL"
+                            ),
+                        );
+                    }
+                    None => panic!(concat!(
+                        "`", stringify!($ty), "::", stringify!($field), "` is missing in WGSL"
+                    )),
+                }
+                index += 1;
+            )*
+            assert!(
+                index == wgsl.members.len(),
+                concat!("WGSL struct has fields missing from `", stringify!($ty), "`"),
+            );
+        };
+    };
+}
+
+assert_wgsl_layout!(GlobalParams, wgsl::GLOBAL_PARAMS, {
+    viewport_size,
+    premultiplied_alpha,
+});
+assert_wgsl_layout!(Bounds<ScaledPixels>, wgsl::BOUNDS, { origin, size });
+assert_wgsl_layout!(Corners<Size<ScaledPixels>>, wgsl::CORNERS, {
+    top_left,
+    top_right,
+    bottom_right,
+    bottom_left,
+});
+assert_wgsl_layout!(Edges<ScaledPixels>, wgsl::EDGES, { top, right, bottom, left });
+assert_wgsl_layout!(Edges<Hsla>, wgsl::EDGE_COLORS, { top, right, bottom, left });
+assert_wgsl_layout!(Hsla, wgsl::HSLA, { h, s, l, a });
+assert_wgsl_layout!(ColorStop, wgsl::COLOR_STOP, { color, percentage });
+assert_wgsl_layout!(BackgroundInstance, wgsl::BACKGROUND, {
+    tag,
+    color_space,
+    angle,
+    stop_offset,
+    center,
+    stop_count,
+    solid,
+});
+assert_wgsl_layout!(QuadInstance, wgsl::QUAD, {
+    order,
+    border_style,
+    bounds,
+    background,
+    border_colors,
+    corner_radii,
+    border_widths,
+    border_dash_length,
+    border_dash_gap,
+    transformation,
+});
+assert_wgsl_layout!(TransformedPathVertex, wgsl::TRANSFORMED_PATH_VERTEX, {
+    xy_position,
+    color,
+    transformation,
+});
+assert_wgsl_layout!(TransformationMatrix, wgsl::TRANSFORMATION_MATRIX, {
+    rotation_scale,
+    translation,
+});
+assert_wgsl_layout!(Underline, wgsl::UNDERLINE, {
+    order,
+    bounds,
+    color,
+    thickness,
+    style,
+});
+assert_wgsl_layout!(BackdropBlur, wgsl::BACKDROP_BLUR, {
+    order,
+    blur_radius,
+    bounds,
+    corner_radii,
+});
+assert_wgsl_layout!(LayerComposite, wgsl::LAYER_COMPOSITE, { bounds, opacity });
@@ -1,109 +1,574 @@
-use core::slice;
-use std::iter::Peekable;
+use std::{cmp::Reverse, iter::Peekable, mem, ops::Range, vec};
 
 use crate::{
-    color::Hsla,
-    geometry::{Bounds, Corners, Edges, ScaledPixels},
+    color::{Background, Hsla},
+    geometry::{Bounds, Corners, Edges, Point, ScaledPixels, Size, TransformationMatrix},
 };
 
 pub(crate) type DrawOrder = u32;
 
+/// The primitives of a frame, in per-kind vectors sorted by draw order.
+///
+/// They're either pushed to the vectors with their orders set by hand, or
+/// [inserted](Scene::insert) within [layers](Scene::push_layer) and given
+/// their orders by [`Scene::finish`].
+#[derive(Clone, Default)]
 pub(crate) struct Scene {
     pub quads: Vec<Quad>,
     pub monochrome_sprites: Vec<MonochromeSprite>,
+    pub paths: Vec<Path>,
+    pub underlines: Vec<Underline>,
+    pub backdrop_blurs: Vec<BackdropBlur>,
+    /// Sorted by the start of their orders, enclosing layers first.
+    pub opacity_layers: Vec<OpacityLayer>,
+    /// What was inserted since the last [`Scene::finish`].
+    pub pending: PendingPrimitives,
 }
 
 impl Scene {
-    pub(crate) fn batches(&self) -> impl IntoIterator<Item = PrimitiveBatch> {
+    /// Starts a stacking context: everything inserted until the matching
+    /// [`Scene::pop_layer`] is drawn together, above the earlier and beneath
+    /// the later children of the enclosing layer with the same `z_index`.
+    ///
+    /// A higher `z_index` draws it over all of its siblings with a lower one,
+    /// whenever they're inserted, e.g. `1` for a tooltip inserted along with
+    /// the widget it belongs to. A negative one draws it beneath the
+    /// primitives inserted into the enclosing layer itself.
+    pub(crate) fn push_layer(&mut self, z_index: i32) {
+        self.pending.push_layer(z_index, false);
+    }
+
+    /// Starts a stacking context like [`Scene::push_layer`] with a `z_index`
+    /// of 0, whose contents are drawn offscreen and composited with `opacity`.
+    /// Anything drawn outside of `bounds` is clipped.
+    pub(crate) fn push_opacity_layer(&mut self, bounds: Bounds<ScaledPixels>, opacity: f32) {
+        self.pending.push_layer(0, true);
+        let mut stacking_order = self.pending.stacking_order();
+        stacking_order.push(StackingIndex::FIRST);
+        self.pending.primitives.push((
+            stacking_order,
+            PendingPrimitive::OpacityLayerStart { bounds, opacity },
+        ));
+    }
+
+    /// Ends the layer pushed last, by [`Scene::push_layer`] or
+    /// [`Scene::push_opacity_layer`].
+    pub(crate) fn pop_layer(&mut self) {
+        let entry = self.pending.layer_stack.last().expect("no layer to pop");
+        if entry.opacity_layer {
+            let mut stacking_order = self.pending.stacking_order();
+            stacking_order.push(StackingIndex::LAST);
+            self.pending
+                .primitives
+                .push((stacking_order, PendingPrimitive::OpacityLayerEnd));
+        }
+        self.pending.layer_stack.pop();
+    }
+
+    /// Adds a primitive above everything inserted into the current layer
+    /// before it. Its draw order is ignored and set by [`Scene::finish`].
+    pub(crate) fn insert(&mut self, primitive: impl Into<Primitive>) {
+        let mut stacking_order = self.pending.stacking_order();
+        stacking_order.push(self.pending.next_index(0));
+        self.pending.primitives.push((
+            stacking_order,
+            PendingPrimitive::Primitive(primitive.into()),
+        ));
+    }
+
+    /// Gives everything inserted since the last call consecutive draw orders,
+    /// by layer and z-index, and sorts every vector by draw order so they can
+    /// be [batched](Scene::batches). Inserted primitives are numbered on from
+    /// the highest order already in the scene, so they're drawn over it.
+    pub(crate) fn finish(&mut self) {
+        assert!(
+            self.pending.layer_stack.is_empty(),
+            "a pushed layer wasn't popped"
+        );
+
+        let mut pending = mem::take(&mut self.pending.primitives);
+        pending.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut order = self.next_order();
+        let mut open_opacity_layers = Vec::new();
+        for (_, pending_primitive) in pending {
+            match pending_primitive {
+                PendingPrimitive::Primitive(primitive) => {
+                    self.push_primitive(primitive, order);
+                    order += 1;
+                }
+                PendingPrimitive::OpacityLayerStart { bounds, opacity } => {
+                    open_opacity_layers.push(self.opacity_layers.len());
+                    self.opacity_layers.push(OpacityLayer {
+                        orders: order..order,
+                        bounds,
+                        opacity,
+                    });
+                }
+                PendingPrimitive::OpacityLayerEnd => {
+                    let ix = open_opacity_layers.pop().unwrap();
+                    self.opacity_layers[ix].orders.end = order;
+                }
+            }
+        }
+        self.pending.next_sequence = 0;
+
+        // Also sorts primitives pushed with their orders set by hand. Stable,
+        // so those sharing an order keep the order they were pushed in.
+        sort_by_order(&mut self.quads);
+        sort_by_order(&mut self.monochrome_sprites);
+        sort_by_order(&mut self.paths);
+        sort_by_order(&mut self.underlines);
+        sort_by_order(&mut self.backdrop_blurs);
+        self.opacity_layers
+            .sort_by_key(|layer| (layer.orders.start, Reverse(layer.orders.end)));
+    }
+
+    /// One past the highest draw order in the scene, or 0 if it's empty.
+    fn next_order(&self) -> DrawOrder {
+        fn after<P: ScenePrimitive>(primitives: &[P]) -> DrawOrder {
+            primitives
+                .iter()
+                .map(|primitive| primitive.order() + 1)
+                .max()
+                .unwrap_or(0)
+        }
+        let layers = self.opacity_layers.iter().map(|layer| layer.orders.end);
+        [
+            after(&self.quads),
+            after(&self.monochrome_sprites),
+            after(&self.paths),
+            after(&self.underlines),
+            after(&self.backdrop_blurs),
+        ]
+        .into_iter()
+        .chain(layers)
+        .max()
+        .unwrap()
+    }
+
+    fn push_primitive(&mut self, primitive: Primitive, order: DrawOrder) {
+        match primitive {
+            Primitive::Quad(quad) => self.quads.push(Quad { order, ..quad }),
+            Primitive::MonochromeSprite(sprite) => self
+                .monochrome_sprites
+                .push(MonochromeSprite { order, ..sprite }),
+            Primitive::Path(path) => self.paths.push(Path { order, ..path }),
+            Primitive::Underline(underline) => {
+                self.underlines.push(Underline { order, ..underline })
+            }
+            Primitive::BackdropBlur(backdrop_blur) => self.backdrop_blurs.push(BackdropBlur {
+                order,
+                ..backdrop_blur
+            }),
+        }
+    }
+
+    /// The primitives in draw order, grouped into runs of the same kind.
+    /// Every vector must be sorted by draw order, as [`Scene::finish`] leaves
+    /// them.
+    pub(crate) fn batches(&self) -> impl IntoIterator<Item = PrimitiveBatch<'_>> {
+        let mut opacity_layer_ends = self
+            .opacity_layers
+            .iter()
+            .filter(|layer| !layer.orders.is_empty())
+            .map(|layer| layer.orders.end)
+            .collect::<Vec<_>>();
+        opacity_layer_ends.sort();
         BatchIterator {
             quads: &self.quads,
-            quads_start: 0,
-            quads_iter: self.quads.iter().peekable(),
             monochrome_sprites: &self.monochrome_sprites,
-            monochrome_sprites_start: 0,
-            monochrome_sprites_iter: self.monochrome_sprites.iter().peekable(),
+            paths: &self.paths,
+            underlines: &self.underlines,
+            backdrop_blurs: &self.backdrop_blurs,
+            opacity_layers: &self.opacity_layers,
+            opacity_layer_ends: opacity_layer_ends.into_iter().peekable(),
         }
     }
+
+    /// Returns the bounds of every primitive that was added, removed or changed
+    /// since `previous`.
+    ///
+    /// Primitives are compared position by position, so reordering them damages
+    /// both their old and new bounds, which is conservative but always correct.
+    ///
+    /// Damage anywhere a backdrop blur samples from also damages everything it
+    /// samples, since the blur is redrawn from what's beneath it this frame.
+    pub(crate) fn damage_since(&self, previous: &Scene) -> Vec<Bounds<ScaledPixels>> {
+        let mut damage = Vec::new();
+        diff_primitives(
+            &previous.quads,
+            &self.quads,
+            Quad::visual_bounds,
+            &mut damage,
+        );
+        diff_primitives(
+            &previous.monochrome_sprites,
+            &self.monochrome_sprites,
+            MonochromeSprite::visual_bounds,
+            &mut damage,
+        );
+        diff_primitives(
+            &previous.paths,
+            &self.paths,
+            Path::visual_bounds,
+            &mut damage,
+        );
+        diff_primitives(
+            &previous.underlines,
+            &self.underlines,
+            |underline| underline.bounds,
+            &mut damage,
+        );
+        diff_primitives(
+            &previous.backdrop_blurs,
+            &self.backdrop_blurs,
+            |backdrop_blur| backdrop_blur.bounds,
+            &mut damage,
+        );
+        diff_primitives(
+            &previous.opacity_layers,
+            &self.opacity_layers,
+            |layer| layer.bounds,
+            &mut damage,
+        );
+
+        // Redrawing a blur's reach may reach another blur in turn.
+        let mut grown = true;
+        while grown {
+            grown = false;
+            for backdrop_blur in &self.backdrop_blurs {
+                let reach = backdrop_blur.reach();
+                if damage.iter().any(|bounds| bounds.intersects(&reach))
+                    && !damage.iter().any(|bounds| bounds.contains(&reach))
+                {
+                    damage.push(reach);
+                    grown = true;
+                }
+            }
+        }
+        damage
+    }
+
+    /// The draw order of the topmost quad, sprite or path under `position`,
+    /// which is mapped into each primitive's own space through the inverse of
+    /// its transformation.
+    pub(crate) fn hit_test(&self, position: Point<ScaledPixels>) -> Option<DrawOrder> {
+        let quads = self
+            .quads
+            .iter()
+            .filter(|quad| quad.contains_point(position))
+            .map(|quad| (quad.order, PrimitiveKind::Quad));
+        let sprites = self
+            .monochrome_sprites
+            .iter()
+            .filter(|sprite| sprite.contains_point(position))
+            .map(|sprite| (sprite.order, PrimitiveKind::MonochromeSprite));
+        let paths = self
+            .paths
+            .iter()
+            .filter(|path| path.contains_point(position))
+            .map(|path| (path.order, PrimitiveKind::Path));
+        quads
+            .chain(sprites)
+            .chain(paths)
+            .max()
+            .map(|(order, _)| order)
+    }
 }
 
-#[derive(Clone, Copy, Default, Ord, PartialEq, Eq, PartialOrd)]
+/// Where an inserted primitive is drawn: its position within each enclosing
+/// layer, outermost first, then within its own layer. Compared
+/// lexicographically, so a layer's contents stay together.
+type StackingOrder = Vec<StackingIndex>;
+
+/// A position among the children of a layer. Those with a higher z-index are
+/// drawn over the others, and those with the same one in the order they were
+/// inserted. Primitives themselves have a z-index of 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct StackingIndex {
+    z_index: i32,
+    sequence: u32,
+}
+
+impl StackingIndex {
+    /// Before and after every child, marking where an opacity layer starts
+    /// and ends.
+    const FIRST: Self = Self {
+        z_index: i32::MIN,
+        sequence: 0,
+    };
+    const LAST: Self = Self {
+        z_index: i32::MAX,
+        sequence: u32::MAX,
+    };
+}
+
+/// The state behind [`Scene::insert`] and the layer stack.
+#[derive(Clone, Default)]
+pub(crate) struct PendingPrimitives {
+    /// The layers pushed and not yet popped, innermost last.
+    layer_stack: Vec<LayerStackEntry>,
+    primitives: Vec<(StackingOrder, PendingPrimitive)>,
+    next_sequence: u32,
+}
+
+impl PendingPrimitives {
+    fn push_layer(&mut self, z_index: i32, opacity_layer: bool) {
+        let index = self.next_index(z_index);
+        self.layer_stack.push(LayerStackEntry {
+            index,
+            opacity_layer,
+        });
+    }
+
+    fn next_index(&mut self, z_index: i32) -> StackingIndex {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        StackingIndex { z_index, sequence }
+    }
+
+    fn stacking_order(&self) -> StackingOrder {
+        self.layer_stack.iter().map(|entry| entry.index).collect()
+    }
+}
+
+#[derive(Clone)]
+struct LayerStackEntry {
+    index: StackingIndex,
+    opacity_layer: bool,
+}
+
+// Mostly primitives, which `Primitive` already holds unboxed.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum PendingPrimitive {
+    Primitive(Primitive),
+    OpacityLayerStart {
+        bounds: Bounds<ScaledPixels>,
+        opacity: f32,
+    },
+    OpacityLayerEnd,
+}
+
+fn diff_primitives<P: PartialEq>(
+    previous: &[P],
+    current: &[P],
+    bounds: impl Fn(&P) -> Bounds<ScaledPixels>,
+    damage: &mut Vec<Bounds<ScaledPixels>>,
+) {
+    for index in 0..previous.len().max(current.len()) {
+        match (previous.get(index), current.get(index)) {
+            (Some(old), Some(new)) if old == new => {}
+            (old, new) => damage.extend(old.into_iter().chain(new).map(&bounds)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Ord, PartialEq, Eq, PartialOrd)]
 pub(crate) enum PrimitiveKind {
+    // Layers end before the next one starts, and both before anything at
+    // their order is drawn.
+    OpacityLayerEnd,
+    OpacityLayerStart,
+    // Before the other primitives, so a panel drawn over its backdrop can
+    // share its draw order.
+    BackdropBlur,
     #[default]
     Quad,
     MonochromeSprite,
+    Path,
+    Underline,
 }
 
+// Only passed by value on the way into a scene's per-kind vectors, where boxing
+// quads would cost an allocation each.
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Ord, PartialEq, Eq, PartialOrd)]
 pub(crate) enum Primitive {
     Quad(Quad),
     MonochromeSprite(MonochromeSprite),
+    Path(Path),
+    Underline(Underline),
+    BackdropBlur(BackdropBlur),
+}
+
+/// A primitive kept in one of a scene's per-kind vectors, which are batched
+/// the same way whatever their kind.
+trait ScenePrimitive {
+    const KIND: PrimitiveKind;
+
+    fn order(&self) -> DrawOrder;
 }
 
+fn sort_by_order<P: ScenePrimitive>(primitives: &mut [P]) {
+    primitives.sort_by_key(P::order);
+}
+
+/// The order and kind of the first of `primitives`, which batches compare
+/// to pick the next kind to draw.
+fn first_order_and_kind<P: ScenePrimitive>(primitives: &[P]) -> Option<(DrawOrder, PrimitiveKind)> {
+    primitives
+        .first()
+        .map(|primitive| (primitive.order(), P::KIND))
+}
+
+/// Splits off the leading run of `primitives` drawn before `until`, the order
+/// and kind of the next primitive of any other kind.
+fn take_batch<'a, P: ScenePrimitive>(
+    primitives: &mut &'a [P],
+    until: Option<(DrawOrder, PrimitiveKind)>,
+) -> &'a [P] {
+    let len = match until {
+        Some(until) => primitives.partition_point(|primitive| (primitive.order(), P::KIND) < until),
+        None => primitives.len(),
+    };
+    let (batch, rest) = primitives.split_at(len);
+    *primitives = rest;
+    batch
+}
+
+/// Yields the next run of one kind until a primitive of another kind comes
+/// first, ordered by draw order and then by [`PrimitiveKind`]. Each field is
+/// what's left to batch of its vector.
 struct BatchIterator<'a> {
     quads: &'a [Quad],
-    quads_start: usize,
-    quads_iter: Peekable<slice::Iter<'a, Quad>>,
     monochrome_sprites: &'a [MonochromeSprite],
-    monochrome_sprites_start: usize,
-    monochrome_sprites_iter: Peekable<slice::Iter<'a, MonochromeSprite>>,
+    paths: &'a [Path],
+    underlines: &'a [Underline],
+    backdrop_blurs: &'a [BackdropBlur],
+    opacity_layers: &'a [OpacityLayer],
+    /// Sorted, leaving out empty layers.
+    opacity_layer_ends: Peekable<vec::IntoIter<DrawOrder>>,
 }
 
 impl<'a> Iterator for BatchIterator<'a> {
     type Item = PrimitiveBatch<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut orders_and_kinds = [
-            (self.quads_iter.peek().map(|q| q.order), PrimitiveKind::Quad),
-            (
-                self.monochrome_sprites_iter.peek().map(|s| s.order),
-                PrimitiveKind::MonochromeSprite,
-            ),
-        ];
-
-        orders_and_kinds.sort_by_key(|(order, kind)| (order.unwrap_or(u32::MAX), *kind));
+        // Empty layers would end before they start.
+        while let Some((layer, rest)) = self.opacity_layers.split_first() {
+            if !layer.orders.is_empty() {
+                break;
+            }
+            self.opacity_layers = rest;
+        }
 
-        let first = orders_and_kinds[0];
-        let second = orders_and_kinds[1];
-        let (batch_kind, max_order_and_kind) = if first.0.is_some() {
-            (first.1, (second.0.unwrap_or(u32::MAX), second.1))
-        } else {
-            return None;
-        };
+        // Each kind's next order; the kinds differ, so there are no ties.
+        let next = [
+            first_order_and_kind(self.quads),
+            first_order_and_kind(self.monochrome_sprites),
+            first_order_and_kind(self.paths),
+            first_order_and_kind(self.underlines),
+            first_order_and_kind(self.backdrop_blurs),
+            self.opacity_layers
+                .first()
+                .map(|layer| (layer.orders.start, PrimitiveKind::OpacityLayerStart)),
+            self.opacity_layer_ends
+                .peek()
+                .map(|end| (*end, PrimitiveKind::OpacityLayerEnd)),
+        ];
+        let (_, kind) = next.iter().flatten().min().copied()?;
+        let until = next
+            .iter()
+            .flatten()
+            .filter(|(_, other)| *other != kind)
+            .min()
+            .copied();
 
-        match batch_kind {
-            PrimitiveKind::Quad => {
-                let quads_start = self.quads_start;
-                let mut quads_end = self.quads_start + 1;
-                self.quads_iter.next();
-                while self
-                    .quads_iter
-                    .next_if(|quad| (quad.order, batch_kind) < max_order_and_kind)
-                    .is_some()
-                {
-                    quads_end += 1;
-                }
-                self.quads_start = quads_end;
-                Some(PrimitiveBatch::Quads(&self.quads[quads_start..quads_end]))
+        Some(match kind {
+            PrimitiveKind::Quad => PrimitiveBatch::Quads(take_batch(&mut self.quads, until)),
+            PrimitiveKind::MonochromeSprite => {
+                PrimitiveBatch::MonochromeSprites(take_batch(&mut self.monochrome_sprites, until))
             }
-            PrimitiveKind::MonochromeSprite => todo!(),
-        }
+            PrimitiveKind::Path => PrimitiveBatch::Paths(take_batch(&mut self.paths, until)),
+            PrimitiveKind::Underline => {
+                PrimitiveBatch::Underlines(take_batch(&mut self.underlines, until))
+            }
+            PrimitiveKind::BackdropBlur => {
+                PrimitiveBatch::BackdropBlurs(take_batch(&mut self.backdrop_blurs, until))
+            }
+            PrimitiveKind::OpacityLayerStart => {
+                let (layer, rest) = self.opacity_layers.split_first().unwrap();
+                self.opacity_layers = rest;
+                PrimitiveBatch::PushOpacityLayer(layer)
+            }
+            PrimitiveKind::OpacityLayerEnd => {
+                self.opacity_layer_ends.next();
+                PrimitiveBatch::PopOpacityLayer
+            }
+        })
     }
 }
 
 pub(crate) enum PrimitiveBatch<'a> {
     Quads(&'a [Quad]),
+    // Only read once the renderer draws sprites.
+    #[allow(dead_code)]
+    MonochromeSprites(&'a [MonochromeSprite]),
+    Paths(&'a [Path]),
+    Underlines(&'a [Underline]),
+    BackdropBlurs(&'a [BackdropBlur]),
+    /// Everything up to the matching pop is drawn into the layer.
+    PushOpacityLayer(&'a OpacityLayer),
+    /// Composites the innermost layer into the one around it.
+    PopOpacityLayer,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-#[repr(C)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub(crate) struct Quad {
     pub order: DrawOrder,
-    pub pad: u32, // align to 8 bytes
+    pub border_style: BorderStyle,
     pub bounds: Bounds<ScaledPixels>,
-    pub background: Hsla,
-    pub border_color: Hsla,
-    pub corner_radii: Corners<ScaledPixels>,
+    pub background: Background,
+    pub border_colors: Edges<Hsla>,
+    /// The horizontal and vertical radius of each corner, which are equal for
+    /// circular corners.
+    pub corner_radii: Corners<Size<ScaledPixels>>,
     pub border_widths: Edges<ScaledPixels>,
+    /// Dashed borders: how long each dash is.
+    pub border_dash_length: ScaledPixels,
+    /// Dashed and dotted borders: the space between dashes or dots.
+    pub border_dash_gap: ScaledPixels,
+    /// Applied to `bounds` and everything in them. Edges are still anti-aliased
+    /// over one pixel of the untransformed quad, so they soften or sharpen a
+    /// little when scaled.
+    pub transformation: TransformationMatrix,
+}
+
+impl Quad {
+    /// The area the quad covers on screen.
+    pub fn visual_bounds(&self) -> Bounds<ScaledPixels> {
+        self.transformation.apply_to_bounds(self.bounds)
+    }
+
+    /// Whether `position` is inside the quad's rounded bounds, once transformed.
+    pub fn contains_point(&self, position: Point<ScaledPixels>) -> bool {
+        let Some(inverse) = self.transformation.invert() else {
+            return false;
+        };
+        let position = inverse.apply(position);
+        // Relative to the center, picking the nearest corner like `quad.wgsl`.
+        let half_width = self.bounds.size.width.0 / 2.0;
+        let half_height = self.bounds.size.height.0 / 2.0;
+        let x = position.x.0 - self.bounds.origin.x.0 - half_width;
+        let y = position.y.0 - self.bounds.origin.y.0 - half_height;
+        if x.abs() > half_width || y.abs() > half_height {
+            return false;
+        }
+        let radius = match (x < 0.0, y < 0.0) {
+            (true, true) => self.corner_radii.top_left,
+            (false, true) => self.corner_radii.top_right,
+            (false, false) => self.corner_radii.bottom_right,
+            (true, false) => self.corner_radii.bottom_left,
+        };
+        let (radius_x, radius_y) = (radius.width.0, radius.height.0);
+        if radius_x <= 0.0 || radius_y <= 0.0 {
+            return true;
+        }
+        let corner_x = (x.abs() - half_width + radius_x) / radius_x;
+        let corner_y = (y.abs() - half_height + radius_y) / radius_y;
+        corner_x <= 0.0 || corner_y <= 0.0 || corner_x * corner_x + corner_y * corner_y <= 1.0
+    }
 }
 
 impl Ord for Quad {
@@ -124,11 +589,53 @@ impl From<Quad> for Primitive {
     }
 }
 
+impl ScenePrimitive for Quad {
+    const KIND: PrimitiveKind = PrimitiveKind::Quad;
+
+    fn order(&self) -> DrawOrder {
+        self.order
+    }
+}
+
+/// How a quad's border is drawn. Dashes and dots run along the rounded
+/// corners, and their spacing is stretched slightly so a whole number of them
+/// fits around the quad.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u32)]
+pub(crate) enum BorderStyle {
+    #[default]
+    Solid,
+    Dashed,
+    /// Round dots as wide as the border.
+    Dotted,
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub(crate) struct MonochromeSprite {
     pub order: DrawOrder,
     pub bounds: Bounds<ScaledPixels>,
     pub color: Hsla,
+    /// Only used for hit testing and damage for now, as sprites aren't drawn
+    /// until there's a glyph atlas.
+    pub transformation: TransformationMatrix,
+}
+
+impl MonochromeSprite {
+    /// The area the sprite covers on screen.
+    pub fn visual_bounds(&self) -> Bounds<ScaledPixels> {
+        self.transformation.apply_to_bounds(self.bounds)
+    }
+
+    /// Whether `position` is inside the sprite's bounds, once transformed.
+    pub fn contains_point(&self, position: Point<ScaledPixels>) -> bool {
+        self.transformation.invert().is_some_and(|inverse| {
+            let position = inverse.apply(position);
+            self.bounds.contains(&Bounds {
+                origin: position,
+                size: Size::default(),
+            })
+        })
+    }
 }
 
 impl Ord for MonochromeSprite {
@@ -148,3 +655,600 @@ impl From<MonochromeSprite> for Primitive {
         Primitive::MonochromeSprite(sprite)
     }
 }
+
+impl ScenePrimitive for MonochromeSprite {
+    const KIND: PrimitiveKind = PrimitiveKind::MonochromeSprite;
+
+    fn order(&self) -> DrawOrder {
+        self.order
+    }
+}
+
+/// A filled or stroked outline, tessellated into triangles by
+/// [`PathBuilder`](crate::path_builder::PathBuilder).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Path {
+    pub order: DrawOrder,
+    /// Of the untransformed vertices.
+    pub bounds: Bounds<ScaledPixels>,
+    pub transformation: TransformationMatrix,
+    /// Three per triangle, unindexed.
+    pub vertices: Vec<PathVertex>,
+}
+
+impl Path {
+    /// The area the path covers on screen.
+    pub fn visual_bounds(&self) -> Bounds<ScaledPixels> {
+        self.transformation.apply_to_bounds(self.bounds)
+    }
+
+    /// Whether `position` is inside one of the path's triangles, once
+    /// transformed.
+    pub fn contains_point(&self, position: Point<ScaledPixels>) -> bool {
+        let Some(inverse) = self.transformation.invert() else {
+            return false;
+        };
+        let position = inverse.apply(position);
+        let (x, y) = (position.x.0, position.y.0);
+        self.vertices.chunks_exact(3).any(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| {
+                let vertex = triangle[i].xy_position;
+                (vertex.x.0, vertex.y.0)
+            });
+            // Which side of each edge the point is on; inside if it's the same
+            // side for all three, whichever way the triangle winds.
+            let side = |(x0, y0): (f32, f32), (x1, y1): (f32, f32)| {
+                (x1 - x0) * (y - y0) - (y1 - y0) * (x - x0)
+            };
+            let sides = [side(a, b), side(b, c), side(c, a)];
+            sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+        })
+    }
+}
+
+impl Ord for Path {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.order.cmp(&other.order)
+    }
+}
+
+impl PartialOrd for Path {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<Path> for Primitive {
+    fn from(path: Path) -> Self {
+        Primitive::Path(path)
+    }
+}
+
+impl ScenePrimitive for Path {
+    const KIND: PrimitiveKind = PrimitiveKind::Path;
+
+    fn order(&self) -> DrawOrder {
+        self.order
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub(crate) struct PathVertex {
+    pub xy_position: Point<ScaledPixels>,
+    pub color: Hsla,
+}
+
+/// A text decoration line. Strikethroughs are underlines placed across the
+/// text instead of below it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub(crate) struct Underline {
+    pub order: DrawOrder,
+    pub pad: u32, // align to 8 bytes
+    /// The area the line is drawn in, centered vertically. Wavy lines
+    /// oscillate between its top and bottom.
+    pub bounds: Bounds<ScaledPixels>,
+    pub color: Hsla,
+    pub thickness: ScaledPixels,
+    pub style: UnderlineStyle,
+}
+
+impl Ord for Underline {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.order.cmp(&other.order)
+    }
+}
+
+impl PartialOrd for Underline {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<Underline> for Primitive {
+    fn from(underline: Underline) -> Self {
+        Primitive::Underline(underline)
+    }
+}
+
+impl ScenePrimitive for Underline {
+    const KIND: PrimitiveKind = PrimitiveKind::Underline;
+
+    fn order(&self) -> DrawOrder {
+        self.order
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u32)]
+pub(crate) enum UnderlineStyle {
+    /// Links and IME preedit text.
+    #[default]
+    Straight,
+    /// Spell-check style errors, with a wavelength of twice the bounds height.
+    Wavy,
+}
+
+/// Blurs everything drawn before it within its rounded bounds, for
+/// frosted-glass panels. The tint is a translucent quad drawn over it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub(crate) struct BackdropBlur {
+    pub order: DrawOrder,
+    /// About how far each pixel is smeared. Each blur pass doubles that, so
+    /// it's rounded up to a power of two, at most `2^MAX_PASSES`.
+    pub blur_radius: ScaledPixels,
+    pub bounds: Bounds<ScaledPixels>,
+    /// The horizontal and vertical radius of each corner, as for quads.
+    pub corner_radii: Corners<Size<ScaledPixels>>,
+}
+
+impl BackdropBlur {
+    pub const MAX_PASSES: u32 = 6;
+
+    /// How many times the backdrop is downsampled, and upsampled again.
+    pub fn passes(&self) -> u32 {
+        (self.blur_radius.0.max(2.0).log2().ceil() as u32).min(Self::MAX_PASSES)
+    }
+
+    /// The area the blur samples from: its bounds, grown by how far the passes
+    /// spread colors. Each pass spreads them by a couple of texels of its level.
+    pub fn reach(&self) -> Bounds<ScaledPixels> {
+        self.bounds
+            .dilate(ScaledPixels((4u32 << self.passes()) as f32))
+    }
+}
+
+impl Ord for BackdropBlur {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.order.cmp(&other.order)
+    }
+}
+
+impl PartialOrd for BackdropBlur {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<BackdropBlur> for Primitive {
+    fn from(backdrop_blur: BackdropBlur) -> Self {
+        Primitive::BackdropBlur(backdrop_blur)
+    }
+}
+
+impl ScenePrimitive for BackdropBlur {
+    const KIND: PrimitiveKind = PrimitiveKind::BackdropBlur;
+
+    fn order(&self) -> DrawOrder {
+        self.order
+    }
+}
+
+/// Groups the primitives whose draw orders fall within `orders`, which are
+/// drawn into an offscreen texture and composited with `opacity`, so that
+/// overlapping children fade as one instead of showing through each other.
+///
+/// Layers may nest, but their orders must not partially overlap.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct OpacityLayer {
+    pub orders: Range<DrawOrder>,
+    /// Anything drawn in the layer outside of these is clipped.
+    pub bounds: Bounds<ScaledPixels>,
+    pub opacity: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use super::*;
+
+    fn point(x: f32, y: f32) -> Point<ScaledPixels> {
+        Point {
+            x: ScaledPixels(x),
+            y: ScaledPixels(y),
+        }
+    }
+
+    fn quad(order: DrawOrder, origin: [f32; 2], size: f32, corner_radius: f32) -> Quad {
+        Quad {
+            order,
+            border_style: BorderStyle::Solid,
+            bounds: Bounds {
+                origin: point(origin[0], origin[1]),
+                size: Size {
+                    width: ScaledPixels(size),
+                    height: ScaledPixels(size),
+                },
+            },
+            background: Hsla::green().into(),
+            border_colors: Edges::all(Hsla::red()),
+            corner_radii: Corners::all(Size::circular(ScaledPixels(corner_radius))),
+            border_widths: Edges::all(ScaledPixels(0.0)),
+            border_dash_length: ScaledPixels(0.0),
+            border_dash_gap: ScaledPixels(0.0),
+            transformation: TransformationMatrix::unit(),
+        }
+    }
+
+    fn path(order: DrawOrder) -> Path {
+        Path {
+            order,
+            bounds: Bounds::default(),
+            transformation: TransformationMatrix::unit(),
+            vertices: Vec::new(),
+        }
+    }
+
+    fn underline(order: DrawOrder) -> Underline {
+        Underline {
+            order,
+            pad: 0,
+            bounds: Bounds::default(),
+            color: Hsla::red(),
+            thickness: ScaledPixels(1.0),
+            style: UnderlineStyle::Straight,
+        }
+    }
+
+    /// Each batch as its kind and the orders in it.
+    fn batch_orders(scene: &Scene) -> Vec<(&'static str, Vec<DrawOrder>)> {
+        fn orders<P: ScenePrimitive>(primitives: &[P]) -> Vec<DrawOrder> {
+            primitives.iter().map(P::order).collect()
+        }
+        scene
+            .batches()
+            .into_iter()
+            .map(|batch| match batch {
+                PrimitiveBatch::Quads(quads) => ("quads", orders(quads)),
+                PrimitiveBatch::MonochromeSprites(sprites) => ("sprites", orders(sprites)),
+                PrimitiveBatch::Paths(paths) => ("paths", orders(paths)),
+                PrimitiveBatch::Underlines(underlines) => ("underlines", orders(underlines)),
+                PrimitiveBatch::BackdropBlurs(blurs) => ("backdrop blurs", orders(blurs)),
+                PrimitiveBatch::PushOpacityLayer(layer) => ("push", vec![layer.orders.start]),
+                PrimitiveBatch::PopOpacityLayer => ("pop", vec![]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_batches_split_where_kinds_interleave() {
+        let scene = Scene {
+            quads: [0, 1, 4, 7]
+                .map(|order| quad(order, [0.0, 0.0], 10.0, 0.0))
+                .into(),
+            paths: vec![path(2), path(3), path(8)],
+            underlines: vec![underline(5), underline(6)],
+            ..Default::default()
+        };
+        assert_eq!(
+            batch_orders(&scene),
+            [
+                ("quads", vec![0, 1]),
+                ("paths", vec![2, 3]),
+                ("quads", vec![4]),
+                ("underlines", vec![5, 6]),
+                ("quads", vec![7]),
+                ("paths", vec![8]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_batches_at_equal_orders_follow_primitive_kind() {
+        let sprite = MonochromeSprite {
+            order: 1,
+            bounds: Bounds::default(),
+            color: Hsla::red(),
+            transformation: TransformationMatrix::unit(),
+        };
+        let backdrop_blur = BackdropBlur {
+            order: 1,
+            blur_radius: ScaledPixels(4.0),
+            bounds: Bounds::default(),
+            corner_radii: Corners::all(Size::default()),
+        };
+        let scene = Scene {
+            quads: vec![
+                quad(1, [0.0, 0.0], 10.0, 0.0),
+                quad(1, [0.0, 0.0], 10.0, 0.0),
+            ],
+            monochrome_sprites: vec![sprite],
+            paths: vec![path(0), path(1)],
+            underlines: vec![underline(1)],
+            backdrop_blurs: vec![backdrop_blur],
+            opacity_layers: vec![OpacityLayer {
+                orders: 1..2,
+                bounds: Bounds::default(),
+                opacity: 0.5,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            batch_orders(&scene),
+            [
+                ("paths", vec![0]),
+                ("push", vec![1]),
+                ("backdrop blurs", vec![1]),
+                ("quads", vec![1, 1]),
+                ("sprites", vec![1]),
+                ("paths", vec![1]),
+                ("underlines", vec![1]),
+                ("pop", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_finish_sorts_primitives_pushed_by_hand() {
+        let mut scene = Scene {
+            quads: vec![
+                quad(3, [0.0, 0.0], 10.0, 0.0),
+                quad(0, [1.0, 0.0], 10.0, 0.0),
+            ],
+            paths: vec![path(2), path(1)],
+            ..Default::default()
+        };
+        scene.quads.push(quad(0, [2.0, 0.0], 10.0, 0.0));
+        scene.finish();
+
+        assert_eq!(
+            batch_orders(&scene),
+            [
+                ("quads", vec![0, 0]),
+                ("paths", vec![1, 2]),
+                ("quads", vec![3]),
+            ]
+        );
+        // Stable among equal orders.
+        assert_eq!(scene.quads[0].bounds.origin.x, ScaledPixels(1.0));
+    }
+
+    fn quads_scene(quads: &[&Quad]) -> Scene {
+        Scene {
+            quads: quads.iter().map(|&quad| quad.clone()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_damage_since_unchanged_scene_is_empty() {
+        let scene = quads_scene(&[&quad(0, [0.0, 0.0], 10.0, 0.0)]);
+        assert_eq!(scene.damage_since(&scene.clone()), []);
+    }
+
+    #[test]
+    fn test_damage_since_added_and_removed_primitives() {
+        let a = quad(0, [0.0, 0.0], 10.0, 0.0);
+        let b = quad(1, [50.0, 50.0], 10.0, 0.0);
+        let before = quads_scene(&[&a]);
+        let after = quads_scene(&[&a, &b]);
+        assert_eq!(after.damage_since(&before), [b.bounds]);
+        assert_eq!(before.damage_since(&after), [b.bounds]);
+    }
+
+    #[test]
+    fn test_damage_since_changed_primitive() {
+        let a = quad(0, [0.0, 0.0], 10.0, 0.0);
+        let mut recolored = a.clone();
+        recolored.background = Hsla::red().into();
+        assert_eq!(
+            quads_scene(&[&recolored]).damage_since(&quads_scene(&[&a])),
+            [a.bounds, a.bounds]
+        );
+
+        // Moving damages where it was and where it is.
+        let moved = quad(0, [30.0, 0.0], 10.0, 0.0);
+        assert_eq!(
+            quads_scene(&[&moved]).damage_since(&quads_scene(&[&a])),
+            [a.bounds, moved.bounds]
+        );
+    }
+
+    #[test]
+    fn test_damage_since_reordered_primitives() {
+        let a = quad(0, [0.0, 0.0], 10.0, 0.0);
+        let b = quad(0, [50.0, 50.0], 10.0, 0.0);
+        let damage = quads_scene(&[&b, &a]).damage_since(&quads_scene(&[&a, &b]));
+        assert!(damage.contains(&a.bounds) && damage.contains(&b.bounds));
+    }
+
+    #[test]
+    fn test_hit_test_picks_topmost() {
+        let scene = Scene {
+            quads: vec![
+                quad(0, [0.0, 0.0], 100.0, 0.0),
+                quad(2, [50.0, 50.0], 100.0, 0.0),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(scene.hit_test(point(25.0, 25.0)), Some(0));
+        assert_eq!(scene.hit_test(point(75.0, 75.0)), Some(2));
+        assert_eq!(scene.hit_test(point(200.0, 200.0)), None);
+    }
+
+    #[test]
+    fn test_hit_test_skips_rounded_corners() {
+        let scene = Scene {
+            quads: vec![quad(0, [0.0, 0.0], 100.0, 50.0)],
+            ..Default::default()
+        };
+        assert_eq!(scene.hit_test(point(50.0, 50.0)), Some(0));
+        assert_eq!(scene.hit_test(point(5.0, 5.0)), None);
+        assert_eq!(scene.hit_test(point(95.0, 50.0)), Some(0));
+    }
+
+    #[test]
+    fn test_hit_test_maps_through_inverse_transformation() {
+        // A diamond: the square rotated by 45 degrees around its center.
+        let mut diamond = quad(0, [0.0, 0.0], 100.0, 0.0);
+        diamond.transformation = TransformationMatrix::unit()
+            .translate(point(50.0, 50.0))
+            .rotate(FRAC_PI_4)
+            .translate(point(-50.0, -50.0));
+        let scene = Scene {
+            quads: vec![diamond],
+            ..Default::default()
+        };
+        // The square's corner, now outside the diamond.
+        assert_eq!(scene.hit_test(point(5.0, 5.0)), None);
+        // Beyond the square's top edge, within the diamond's top point.
+        assert_eq!(scene.hit_test(point(50.0, -10.0)), Some(0));
+    }
+
+    #[test]
+    fn test_hit_test_maps_sprites_through_inverse_transformation() {
+        // A glyph stretched to twice its width, over a quad.
+        let sprite = MonochromeSprite {
+            order: 1,
+            bounds: Bounds {
+                origin: point(10.0, 10.0),
+                size: Size {
+                    width: ScaledPixels(20.0),
+                    height: ScaledPixels(20.0),
+                },
+            },
+            color: Hsla::red(),
+            transformation: TransformationMatrix::unit().scale(Size {
+                width: 2.0,
+                height: 1.0,
+            }),
+        };
+        let scene = Scene {
+            quads: vec![quad(0, [0.0, 0.0], 100.0, 0.0)],
+            monochrome_sprites: vec![sprite],
+            ..Default::default()
+        };
+        assert_eq!(sprite.visual_bounds().origin, point(20.0, 10.0));
+        // Left of the stretched sprite, though within its untransformed bounds.
+        assert_eq!(scene.hit_test(point(15.0, 20.0)), Some(0));
+        // Right of its untransformed bounds, within the stretched ones.
+        assert_eq!(scene.hit_test(point(50.0, 20.0)), Some(1));
+    }
+
+    #[test]
+    fn test_opacity_layers_wrap_the_batches_in_their_orders() {
+        let layer = |orders: Range<DrawOrder>| OpacityLayer {
+            orders,
+            bounds: Bounds::default(),
+            opacity: 0.5,
+        };
+        let scene = Scene {
+            quads: (0..4)
+                .map(|order| quad(order, [0.0, 0.0], 10.0, 0.0))
+                .collect(),
+            // Nested, and an empty one that's left out.
+            opacity_layers: vec![layer(1..3), layer(2..2), layer(2..3)],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            batch_orders(&scene),
+            [
+                ("quads", vec![0]),
+                ("push", vec![1]),
+                ("quads", vec![1]),
+                ("push", vec![2]),
+                ("quads", vec![2]),
+                ("pop", vec![]),
+                ("pop", vec![]),
+                ("quads", vec![3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layers_draw_by_z_index_then_insertion() {
+        let mut scene = Scene::default();
+        scene.insert(quad(0, [0.0, 0.0], 10.0, 0.0));
+        scene.push_layer(1);
+        scene.insert(quad(0, [1.0, 0.0], 10.0, 0.0));
+        scene.pop_layer();
+        scene.push_layer(0);
+        scene.insert(quad(0, [2.0, 0.0], 10.0, 0.0));
+        // Stays above the whole layer inserted before it.
+        scene.push_layer(5);
+        scene.insert(quad(0, [3.0, 0.0], 10.0, 0.0));
+        scene.pop_layer();
+        scene.pop_layer();
+        scene.insert(quad(0, [4.0, 0.0], 10.0, 0.0));
+        scene.push_layer(-1);
+        scene.insert(quad(0, [5.0, 0.0], 10.0, 0.0));
+        scene.pop_layer();
+        scene.finish();
+
+        let drawn = scene
+            .quads
+            .iter()
+            .map(|quad| (quad.order, quad.bounds.origin.x.0 as u32))
+            .collect::<Vec<_>>();
+        assert_eq!(drawn, [(0, 5), (1, 0), (2, 2), (3, 3), (4, 4), (5, 1)]);
+    }
+
+    #[test]
+    fn test_opacity_layer_spans_the_orders_of_its_contents() {
+        let bounds = Bounds::default();
+        let mut scene = Scene::default();
+        scene.insert(quad(0, [0.0, 0.0], 10.0, 0.0));
+        scene.push_opacity_layer(bounds, 0.5);
+        scene.insert(quad(0, [0.0, 0.0], 10.0, 0.0));
+        scene.push_opacity_layer(bounds, 0.25);
+        scene.insert(quad(0, [0.0, 0.0], 10.0, 0.0));
+        scene.pop_layer();
+        scene.pop_layer();
+        scene.insert(quad(0, [0.0, 0.0], 10.0, 0.0));
+        scene.finish();
+
+        let orders = scene
+            .opacity_layers
+            .iter()
+            .map(|layer| (layer.orders.clone(), layer.opacity))
+            .collect::<Vec<_>>();
+        assert_eq!(orders, [(1..3, 0.5), (2..3, 0.25)]);
+    }
+
+    #[test]
+    fn test_finish_twice_numbers_on() {
+        let mut scene = Scene::default();
+        scene.push_opacity_layer(Bounds::default(), 0.5);
+        scene.insert(quad(0, [0.0, 0.0], 10.0, 0.0));
+        scene.pop_layer();
+        scene.finish();
+        // Even beneath, a later insertion can't share orders with earlier ones.
+        scene.push_layer(-1);
+        scene.insert(quad(0, [1.0, 0.0], 10.0, 0.0));
+        scene.insert(quad(0, [2.0, 0.0], 10.0, 0.0));
+        scene.pop_layer();
+        scene.finish();
+
+        let drawn = scene
+            .quads
+            .iter()
+            .map(|quad| (quad.order, quad.bounds.origin.x.0 as u32))
+            .collect::<Vec<_>>();
+        assert_eq!(drawn, [(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(scene.opacity_layers[0].orders, 0..1);
+    }
+}